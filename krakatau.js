@@ -32,6 +32,8 @@ class KrakatauDecompiler {
                 this.wasmInstance = await WebAssembly.instantiate(this.wasmModule, {
                     env: {
                         abort: () => { throw new Error('WASM abort'); },
+                        // Only used by assemble_streaming, which this wrapper doesn't call
+                        write_chunk: () => -1,
                         memory: mem
                     }
                 });
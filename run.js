@@ -54,7 +54,9 @@ async function run() {
                 abort: () => {
                     console.error('WASM abort called');
                     throw new Error('WASM abort');
-                }
+                },
+                // Only used by assemble_streaming
+                write_chunk: () => -1
             }
         });
 
@@ -59,7 +59,9 @@ class KrakatauServer {
             // Create instance with minimal required imports
             this.wasmInstance = await WebAssembly.instantiate(this.wasmModule, {
                 env: {
-                    abort: () => { throw new Error('WASM abort'); }
+                    abort: () => { throw new Error('WASM abort'); },
+                    // Only used by assemble_streaming
+                    write_chunk: () => -1
                 }
             });
 
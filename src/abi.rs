@@ -0,0 +1,180 @@
+// Description of the functions the module exports, for hosts generating bindings. This is maintained
+// by hand, so every export added to lib.rs needs an entry here as well.
+
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct Param {
+    pub name: &'static str,
+    /// Rust type of the parameter. Pointers and usize are 32 bit under wasm32.
+    #[serde(rename = "type")]
+    pub ty: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Export {
+    pub name: &'static str,
+    pub params: &'static [Param],
+    /// Rust return type, or "()" for none
+    pub returns: &'static str,
+    /// What the return value means, and who owns any memory it points to and for how long
+    pub notes: &'static str,
+}
+
+const fn param(name: &'static str, ty: &'static str) -> Param {
+    Param { name, ty }
+}
+
+const JSON_REQUEST: &[Param] = &[param("json_ptr", "*const u8"), param("json_len", "usize")];
+const RAW_INPUT: &[Param] = &[param("ptr", "*const u8"), param("len", "usize")];
+const SESSION: &[Param] = &[param("session_id", "i32")];
+
+/// Notes shared by every function which stores a JSON response
+const STORES_RESPONSE: &str = "Returns the length of the stored JSON response, read through get_response_ptr, or -1 if \
+    there was no input or the response couldn't be stored";
+
+const fn json_export(name: &'static str) -> Export {
+    Export {
+        name,
+        params: JSON_REQUEST,
+        returns: "i32",
+        notes: STORES_RESPONSE,
+    }
+}
+
+const fn response_export(name: &'static str) -> Export {
+    Export {
+        name,
+        params: &[],
+        returns: "i32",
+        notes: STORES_RESPONSE,
+    }
+}
+
+pub static EXPORTS: &[Export] = &[
+    Export {
+        name: "allocate_input_buffer",
+        params: &[param("data_len", "usize")],
+        returns: "*mut u8",
+        notes: "Allocates a buffer of data_len bytes, less than 65536, for the host to write input into. Returns null \
+            on failure. The host owns the buffer and must release it with free_buffer, passing the same length.",
+    },
+    Export {
+        name: "free_buffer",
+        params: &[param("ptr", "*mut u8"), param("len", "usize")],
+        returns: "()",
+        notes: "Frees a buffer returned by allocate_input_buffer",
+    },
+    Export {
+        name: "set_output_hint",
+        params: &[param("bytes", "usize")],
+        returns: "()",
+        notes: "Pre-reserves the disassembly output buffer",
+    },
+    json_export("decompile_json"),
+    json_export("assemble_json"),
+    json_export("assemble_jar_json"),
+    json_export("assemble_size_estimate_json"),
+    json_export("assemble_fragments_json"),
+    json_export("split_assembly_json"),
+    Export {
+        name: "assemble_session_begin",
+        params: &[],
+        returns: "i32",
+        notes: "Returns the id of a new session, which stays alive until assemble_session_end",
+    },
+    json_export("assemble_session_update"),
+    Export {
+        name: "assemble_session_build",
+        params: SESSION,
+        returns: "i32",
+        notes: STORES_RESPONSE,
+    },
+    Export {
+        name: "assemble_session_end",
+        params: SESSION,
+        returns: "i32",
+        notes: "Returns 1 if the session existed, 0 otherwise",
+    },
+    json_export("decompile_jar_json"),
+    json_export("reduce_failure_json"),
+    json_export("patch_method_json"),
+    json_export("constant_pool_json"),
+    json_export("layout_json"),
+    json_export("metadata_json"),
+    json_export("instructions_json"),
+    json_export("jump_targets_json"),
+    json_export("call_graph_json"),
+    json_export("code_size_json"),
+    json_export("api_usage_json"),
+    json_export("structural_hash_json"),
+    json_export("relocate_package_json"),
+    json_export("roundtrip_diff_json"),
+    json_export("synthesize_opcode_class_json"),
+    json_export("canonicalize_class_json"),
+    json_export("set_source_file_json"),
+    json_export("minify_class_json"),
+    response_export("version_info_json"),
+    response_export("supported_attributes"),
+    response_export("abi_json"),
+    Export {
+        name: "benchmark_decompile",
+        params: &[param("ptr", "*const u8"), param("len", "usize"), param("iterations", "u32")],
+        returns: "i64",
+        notes: "Takes raw class bytes. Returns the total output size, -1 for no input or -2 if disassembly fails.",
+    },
+    Export {
+        name: "assemble_streaming",
+        params: &[param("src_ptr", "*const u8"), param("src_len", "usize")],
+        returns: "i32",
+        notes: "Takes raw source and passes each class to the env.write_chunk import, which must be provided. Returns \
+            the number of classes written, -1 for no input, -2 if assembly fails (storing the error as the response) \
+            or -3 if a write fails.",
+    },
+    Export {
+        name: "validate_base64",
+        params: RAW_INPUT,
+        returns: "i32",
+        notes: "Returns the decoded length, or -1 for null input, -2 for an invalid character, -3 for invalid padding \
+            and -4 for an impossible length",
+    },
+    Export {
+        name: "get_response_length",
+        params: &[],
+        returns: "i32",
+        notes: "Returns the length of the stored response, or 0 if there is none",
+    },
+    Export {
+        name: "get_response_ptr",
+        params: &[],
+        returns: "*const u8",
+        notes: "Returns the stored response, or null if there is none. The module owns it, and it stays valid until \
+            the next call which stores a response, or free_response.",
+    },
+    Export {
+        name: "has_response",
+        params: &[],
+        returns: "i32",
+        notes: "Returns 1 if a response is stored, 0 otherwise",
+    },
+    Export {
+        name: "get_response_chunk",
+        params: &[param("offset", "usize"), param("max_len", "usize")],
+        returns: "i32",
+        notes: "Copies part of the stored response for get_response_chunk_ptr. Returns the number of bytes copied, \
+            or -1 if offset is past the end.",
+    },
+    Export {
+        name: "get_response_chunk_ptr",
+        params: &[],
+        returns: "*const u8",
+        notes: "Returns the bytes copied by get_response_chunk. The module owns them, and they stay valid until the \
+            next call to get_response_chunk.",
+    },
+    Export {
+        name: "free_response",
+        params: &[],
+        returns: "()",
+        notes: "Frees the stored response, invalidating the pointer returned by get_response_ptr",
+    },
+];
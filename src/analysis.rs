@@ -0,0 +1,342 @@
+// Single passes over parsed bytecode, without any control or data flow analysis.
+use std::collections::{BTreeSet, HashSet};
+
+use serde::Serialize;
+
+use crate::krakatau_lib::classfile::attrs::AttrBody;
+use crate::krakatau_lib::classfile::code::{Code, Instr, Pos, SwitchArena, WideInstr};
+use crate::krakatau_lib::classfile::cpool::{Const, ConstPool};
+use crate::krakatau_lib::classfile::parse::{Class, Field};
+use crate::metadata::{cls, utf};
+use crate::pool::{self, PoolValue};
+
+pub fn method_code<'a>(m: &'a Field<'a>) -> Option<&'a Code<'a>> {
+    m.attrs.iter().find_map(|a| match &a.body {
+        AttrBody::Code((code, _)) => Some(&**code),
+        _ => None,
+    })
+}
+
+/// "name(desc)" signature of a method declared in the class
+pub fn method_sig(cp: &ConstPool, m: &Field) -> String {
+    format!("{}{}", utf(cp, m.name).unwrap_or_default(), utf(cp, m.desc).unwrap_or_default())
+}
+
+/// A Methodref or InterfaceMethodref constant, resolved to strings
+pub struct MethodRef {
+    pub owner: String,
+    pub name: String,
+    pub desc: String,
+}
+
+pub fn method_ref(cp: &ConstPool, ind: u16) -> Option<MethodRef> {
+    let (cls_ind, nat_ind) = match cp.0.get(ind as usize)? {
+        Const::Method(c, nat) | Const::InterfaceMethod(c, nat) => (*c, *nat),
+        _ => return None,
+    };
+    let (name, desc) = match cp.0.get(nat_ind as usize)? {
+        Const::NameAndType(n, t) => (*n, *t),
+        _ => return None,
+    };
+    Some(MethodRef {
+        owner: cls(cp, cls_ind)?,
+        name: utf(cp, name)?,
+        desc: utf(cp, desc)?,
+    })
+}
+
+/// Constant pool indices of the methods invoked by invokevirtual/special/static/interface
+pub fn invoke_targets<'a>(code: &'a Code) -> impl Iterator<Item = u16> + 'a {
+    code.bytecode.0.iter().filter_map(|(_, instr)| match instr {
+        Instr::Invokevirtual(r) | Instr::Invokespecial(r) | Instr::Invokestatic(r) | Instr::Invokeinterface(r, _) => Some(*r),
+        _ => None,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JumpKind {
+    Conditional,
+    /// Includes jsr
+    Unconditional,
+    Switch,
+    ExceptionHandler,
+}
+
+/// Kind and targets of a jump instruction, or None if the instruction always continues to the next one
+fn jumps(instr: &Instr, switches: &SwitchArena) -> Option<(JumpKind, Vec<Pos>)> {
+    use Instr::*;
+    Some(match instr {
+        Ifeq(p) | Ifne(p) | Iflt(p) | Ifge(p) | Ifgt(p) | Ifle(p) | IfIcmpeq(p) | IfIcmpne(p) | IfIcmplt(p)
+        | IfIcmpge(p) | IfIcmpgt(p) | IfIcmple(p) | IfAcmpeq(p) | IfAcmpne(p) | Ifnull(p) | Ifnonnull(p) => {
+            (JumpKind::Conditional, vec![*p])
+        }
+        Goto(p) | GotoW(p) | Jsr(p) | JsrW(p) => (JumpKind::Unconditional, vec![*p]),
+        Tableswitch(i) => {
+            let table = switches.table(*i);
+            (JumpKind::Switch, table.table.iter().copied().chain([table.default]).collect())
+        }
+        Lookupswitch(i) => {
+            let map = switches.map(*i);
+            (JumpKind::Switch, map.table.iter().map(|&(_, p)| p).chain([map.default]).collect())
+        }
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct SwitchCase {
+    pub key: i32,
+    pub target: u32,
+}
+
+/// An instruction operand, tagged with its `kind`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Operand {
+    /// An immediate value, such as the increment of iinc or the dimensions of multianewarray
+    Int { value: i32 },
+    /// A local variable slot
+    Local { index: u16 },
+    /// A constant pool entry, resolved the same way as in constant_pool_json. Null if the index is invalid.
+    Constant { index: u16, constant: Option<PoolValue> },
+    /// The bytecode offset of a jump target
+    Target { offset: u32 },
+    /// The element type of newarray, such as "int"
+    ArrayType { name: String },
+    /// The targets of a tableswitch or lookupswitch, with tableswitch keys listed explicitly
+    Switch { default: u32, cases: Vec<SwitchCase> },
+}
+
+#[derive(Debug, Serialize)]
+pub struct Instruction {
+    pub offset: u32,
+    /// For wide instructions, the mnemonic of the instruction the prefix modifies
+    pub opcode: &'static str,
+    pub wide: bool,
+    pub operands: Vec<Operand>,
+}
+
+fn wide_operands(instr: &WideInstr) -> Vec<Operand> {
+    use WideInstr::*;
+    match *instr {
+        Iload(i) | Lload(i) | Fload(i) | Dload(i) | Aload(i) | Istore(i) | Lstore(i) | Fstore(i) | Dstore(i) | Astore(i)
+        | Ret(i) => vec![Operand::Local { index: i }],
+        Iinc(i, v) => vec![Operand::Local { index: i }, Operand::Int { value: v.into() }],
+    }
+}
+
+fn operands(cp: &ConstPool, instr: &Instr, switches: &SwitchArena) -> Vec<Operand> {
+    use Instr::*;
+    let constant = |index: u16| Operand::Constant {
+        index,
+        constant: pool::constant(cp, index),
+    };
+    let target = |p: &Pos| Operand::Target { offset: p.offset() };
+    match instr {
+        Bipush(v) => vec![Operand::Int { value: (*v).into() }],
+        Sipush(v) => vec![Operand::Int { value: (*v).into() }],
+        Ldc(i) => vec![constant((*i).into())],
+        LdcW(i) | Ldc2W(i) | Getstatic(i) | Putstatic(i) | Getfield(i) | Putfield(i) | Invokevirtual(i)
+        | Invokespecial(i) | Invokestatic(i) | Invokedynamic(i) | New(i) | Anewarray(i) | Checkcast(i) | Instanceof(i) => {
+            vec![constant(*i)]
+        }
+        Invokeinterface(i, count) => vec![constant(*i), Operand::Int { value: (*count).into() }],
+        Multianewarray(i, dims) => vec![constant(*i), Operand::Int { value: (*dims).into() }],
+        Iload(i) | Lload(i) | Fload(i) | Dload(i) | Aload(i) | Istore(i) | Lstore(i) | Fstore(i) | Dstore(i) | Astore(i)
+        | Ret(i) => vec![Operand::Local { index: (*i).into() }],
+        Iinc(i, v) => vec![Operand::Local { index: (*i).into() }, Operand::Int { value: (*v).into() }],
+        Ifeq(p) | Ifne(p) | Iflt(p) | Ifge(p) | Ifgt(p) | Ifle(p) | IfIcmpeq(p) | IfIcmpne(p) | IfIcmplt(p)
+        | IfIcmpge(p) | IfIcmpgt(p) | IfIcmple(p) | IfAcmpeq(p) | IfAcmpne(p) | Ifnull(p) | Ifnonnull(p) | Goto(p)
+        | GotoW(p) | Jsr(p) | JsrW(p) => vec![target(p)],
+        Tableswitch(i) => {
+            let table = switches.table(*i);
+            let cases = table.table.iter().zip(0..).map(|(p, i)| SwitchCase {
+                key: table.low.wrapping_add(i),
+                target: p.offset(),
+            });
+            vec![Operand::Switch {
+                default: table.default.offset(),
+                cases: cases.collect(),
+            }]
+        }
+        Lookupswitch(i) => {
+            let map = switches.map(*i);
+            let cases = map.table.iter().map(|&(key, p)| SwitchCase { key, target: p.offset() });
+            vec![Operand::Switch {
+                default: map.default.offset(),
+                cases: cases.collect(),
+            }]
+        }
+        Newarray(tag) => vec![Operand::ArrayType { name: tag.to_string() }],
+        Wide(w) => wide_operands(w),
+        _ => Vec::new(),
+    }
+}
+
+/// Each instruction of a method, decoded from the parsed bytecode.
+pub fn instructions(cp: &ConstPool, code: &Code) -> Vec<Instruction> {
+    code.bytecode
+        .0
+        .iter()
+        .map(|(pos, instr)| {
+            let (opcode, wide) = match instr {
+                Instr::Wide(w) => (w.mnemonic(), true),
+                _ => (instr.mnemonic(), false),
+            };
+            Instruction {
+                offset: pos.offset(),
+                opcode,
+                wide,
+                operands: operands(cp, instr, &code.bytecode.2),
+            }
+        })
+        .collect()
+}
+
+pub fn find_method<'a>(c: &'a Class<'a>, name: &str, desc: &str) -> Option<&'a Field<'a>> {
+    c.methods
+        .iter()
+        .find(|m| utf(&c.cp, m.name).as_deref() == Some(name) && utf(&c.cp, m.desc).as_deref() == Some(desc))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct JumpEdge {
+    /// For exception handlers, the start of the protected range
+    pub from_offset: u32,
+    pub to_offset: u32,
+    pub kind: JumpKind,
+}
+
+/// Sorted offsets of every jump target and exception handler in a method, along with the edges
+/// leading to them, each reported once.
+pub fn jump_edges(code: &Code) -> (Vec<u32>, Vec<JumpEdge>) {
+    let mut edges = Vec::new();
+    let mut seen = HashSet::new();
+    for (pos, instr) in &code.bytecode.0 {
+        if let Some((kind, targets)) = jumps(instr, &code.bytecode.2) {
+            for target in targets {
+                let edge = JumpEdge {
+                    from_offset: pos.offset(),
+                    to_offset: target.offset(),
+                    kind,
+                };
+                if seen.insert(edge) {
+                    edges.push(edge);
+                }
+            }
+        }
+    }
+    for except in &code.exceptions {
+        let edge = JumpEdge {
+            from_offset: except.start.offset(),
+            to_offset: except.handler.offset(),
+            kind: JumpKind::ExceptionHandler,
+        };
+        if seen.insert(edge) {
+            edges.push(edge);
+        }
+    }
+
+    let targets: BTreeSet<_> = edges.iter().map(|e| e.to_offset).collect();
+    (targets.into_iter().collect(), edges)
+}
+
+/// "name(desc)" of each method which needs a StackMapTable to pass verification but doesn't have
+/// one. From version 50 on, that's any method with jumps or exception handlers.
+pub fn missing_frames(c: &Class) -> Vec<String> {
+    if c.version.0 < 50 {
+        return Vec::new();
+    }
+    c.methods
+        .iter()
+        .filter(|m| {
+            method_code(m).is_some_and(|code| {
+                let branches = !code.exceptions.is_empty()
+                    || code.bytecode.0.iter().any(|(_, instr)| jumps(instr, &code.bytecode.2).is_some());
+                branches && !code.attrs.iter().any(|a| matches!(a.body, AttrBody::StackMapTable(..)))
+            })
+        })
+        .map(|m| method_sig(&c.cp, m))
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct CallEdge {
+    pub from: String,
+    /// "name(desc)" for methods of the same class, "owner.name(desc)" otherwise
+    pub to: String,
+    pub external: bool,
+}
+
+/// Caller to callee edges between the methods of a class, each reported once, in order of first call.
+pub fn call_graph(c: &Class, include_external: bool) -> Vec<CallEdge> {
+    let this = cls(&c.cp, c.this);
+    let mut edges = Vec::new();
+    let mut seen = HashSet::new();
+    for m in &c.methods {
+        let code = match method_code(m) {
+            Some(code) => code,
+            None => continue,
+        };
+        let from = method_sig(&c.cp, m);
+        for target in invoke_targets(code).filter_map(|r| method_ref(&c.cp, r)) {
+            let external = this.as_ref() != Some(&target.owner);
+            if external && !include_external {
+                continue;
+            }
+            let to = if external {
+                format!("{}.{}{}", target.owner, target.name, target.desc)
+            } else {
+                format!("{}{}", target.name, target.desc)
+            };
+            let edge = CallEdge {
+                from: from.clone(),
+                to,
+                external,
+            };
+            if seen.insert(edge.clone()) {
+                edges.push(edge);
+            }
+        }
+    }
+    edges
+}
+
+/// Sorted, deduplicated "owner#name desc" of each method of another class invoked by the class,
+/// restricted to owners starting with one of `prefixes`.
+pub fn api_usage(c: &Class, prefixes: &[String]) -> Vec<String> {
+    let this = cls(&c.cp, c.this);
+    let mut used = BTreeSet::new();
+    for code in c.methods.iter().filter_map(method_code) {
+        for target in invoke_targets(code).filter_map(|r| method_ref(&c.cp, r)) {
+            if this.as_ref() != Some(&target.owner) && prefixes.iter().any(|p| target.owner.starts_with(p.as_str())) {
+                used.insert(format!("{}#{} {}", target.owner, target.name, target.desc));
+            }
+        }
+    }
+    used.into_iter().collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MethodCodeSize {
+    /// "name(desc)"
+    pub method: String,
+    pub code_length: u32,
+}
+
+/// Bytecode length of each method with code, largest first.
+pub fn code_sizes(c: &Class) -> Vec<MethodCodeSize> {
+    let mut sizes: Vec<_> = c
+        .methods
+        .iter()
+        .filter_map(|m| {
+            Some(MethodCodeSize {
+                method: method_sig(&c.cp, m),
+                code_length: method_code(m)?.bytecode.1.offset(),
+            })
+        })
+        .collect();
+    sizes.sort_by(|a, b| b.code_length.cmp(&a.code_length));
+    sizes
+}
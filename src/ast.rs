@@ -0,0 +1,71 @@
+// Structured form of a class's disassembly, for clients which want to work with the class rather than
+// with the text. Operands are kept in assembler syntax, so they match what the text output shows.
+use serde::Serialize;
+
+use crate::krakatau_lib::classfile::parse::{Class, Field};
+use crate::krakatau_lib::{instructions, DisassemblerOptions};
+use crate::metadata::{cls, utf};
+
+#[derive(Debug, Serialize)]
+pub struct ClassAst {
+    /// (major, minor)
+    pub version: (u16, u16),
+    pub access: u16,
+    pub name: Option<String>,
+    /// Null for java/lang/Object and module-info
+    pub super_class: Option<String>,
+    pub interfaces: Vec<Option<String>>,
+    pub fields: Vec<MemberAst>,
+    pub methods: Vec<MemberAst>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemberAst {
+    pub access: u16,
+    pub name: Option<String>,
+    pub descriptor: Option<String>,
+    /// Only set for methods with code
+    pub instructions: Option<Vec<InstructionAst>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InstructionAst {
+    pub offset: u32,
+    pub opcode: String,
+    pub operands: String,
+}
+
+fn member(c: &Class, m: &Field, code: Option<&DisassemblerOptions>) -> Result<MemberAst, String> {
+    let instructions = match code {
+        Some(opts) => instructions(c, m, opts.clone()).map_err(|e| format!("Decompilation error: {}", e))?,
+        None => None,
+    };
+    Ok(MemberAst {
+        access: m.access,
+        name: utf(&c.cp, m.name),
+        descriptor: utf(&c.cp, m.desc),
+        instructions: instructions.map(|instrs| {
+            instrs
+                .into_iter()
+                .map(|(offset, opcode, operands)| InstructionAst {
+                    offset,
+                    opcode,
+                    operands,
+                })
+                .collect()
+        }),
+    })
+}
+
+/// Builds the AST of an already parsed class, printing operands with the same options as the text output.
+pub fn class_ast(c: &Class, opts: &DisassemblerOptions) -> Result<ClassAst, String> {
+    Ok(ClassAst {
+        version: c.version,
+        access: c.access,
+        name: cls(&c.cp, c.this),
+        super_class: if c.super_ == 0 { None } else { cls(&c.cp, c.super_) },
+        interfaces: c.interfaces.iter().map(|&ind| cls(&c.cp, ind)).collect(),
+        fields: c.fields.iter().map(|f| member(c, f, None)).collect::<Result<_, _>>()?,
+        methods: c.methods.iter().map(|m| member(c, m, Some(opts))).collect::<Result<_, _>>()?,
+    })
+}
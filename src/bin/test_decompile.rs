@@ -127,6 +127,7 @@ fn decompile_json_json(request_json: &str) -> String {
     // Set up decompilation options using original library types
     let opts = DisassemblerOptions {
         roundtrip: request.roundtrip,
+        ..Default::default()
     };
     let parse_opts = ParserOptions {
         no_short_code_attr: request.no_short_code_attr,
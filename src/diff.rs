@@ -0,0 +1,106 @@
+// Byte level comparison of a class with the result of disassembling and reassembling it, for tracking
+// down where a roundtrip loses fidelity.
+use serde::Serialize;
+
+use crate::krakatau_lib::{assemble, disassemble, AssemblerOptions, DisassemblerOptions, ParserOptions};
+use crate::raw_class;
+
+/// Differing runs separated by fewer equal bytes than this are reported as a single range
+const MERGE_GAP: usize = 8;
+/// Bytes shown from each side of a range
+const MAX_SHOWN_BYTES: usize = 64;
+
+#[derive(Debug, Serialize)]
+pub struct DiffRange {
+    pub offset: usize,
+    /// Length of the range within each class. These differ when the range runs past the end of the shorter one.
+    pub original_length: usize,
+    pub new_length: usize,
+    /// Hex, cut off after the first 64 bytes
+    pub original_bytes: String,
+    pub new_bytes: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoundtripDiff {
+    pub identical: bool,
+    pub original_size: usize,
+    pub new_size: usize,
+    /// Top level sections, as named by `layout_json`, whose bytes differ
+    pub changed_sections: Vec<&'static str>,
+    /// Byte ranges which differ at the same offset, in order
+    pub ranges: Vec<DiffRange>,
+    /// Whether there were more than `max_ranges` ranges
+    pub truncated: bool,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().take(MAX_SHOWN_BYTES).map(|b| format!("{:02x}", b)).collect()
+}
+
+fn range(a: &[u8], b: &[u8], start: usize, end: usize) -> DiffRange {
+    let side = |data: &[u8]| &data[start.min(data.len())..end.min(data.len())];
+    let (old, new) = (side(a), side(b));
+    DiffRange {
+        offset: start,
+        original_length: old.len(),
+        new_length: new.len(),
+        original_bytes: hex(old),
+        new_bytes: hex(new),
+    }
+}
+
+/// Compares the two byte strings position by position, returning at most `max_ranges` ranges and
+/// whether any were left out.
+fn byte_ranges(a: &[u8], b: &[u8], max_ranges: usize) -> (Vec<DiffRange>, bool) {
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    let mut truncated = false;
+    for i in 0..a.len().max(b.len()) {
+        if a.get(i) == b.get(i) {
+            continue;
+        }
+        match spans.last_mut() {
+            Some((_, end)) if i - *end < MERGE_GAP => *end = i + 1,
+            _ if spans.len() == max_ranges => {
+                truncated = true;
+                break;
+            }
+            _ => spans.push((i, i + 1)),
+        }
+    }
+    (spans.into_iter().map(|(start, end)| range(a, b, start, end)).collect(), truncated)
+}
+
+/// Disassembles and reassembles a class with the given options, and reports how the result differs.
+pub fn roundtrip_diff(
+    data: &[u8],
+    parse_opts: ParserOptions,
+    opts: DisassemblerOptions,
+    max_ranges: usize,
+) -> Result<RoundtripDiff, String> {
+    let original_sections = raw_class::layout(data).map_err(|e| format!("Class parse error: {}", e))?;
+    let (_, text) = disassemble(data, parse_opts, opts).map_err(|e| format!("Decompilation error: {:?}", e))?;
+    let text = String::from_utf8(text).map_err(|e| format!("Output encoding error: {}", e))?;
+    let mut classes = assemble(&text, AssemblerOptions {}).map_err(|e| format!("Assembly error: {:?}", e))?;
+    if classes.len() != 1 {
+        return Err(format!("Expected one class, got {}", classes.len()));
+    }
+    let new = classes.pop().unwrap().1;
+    let new_sections = raw_class::layout(&new).map_err(|e| format!("Class parse error: {}", e))?;
+
+    let changed_sections = original_sections
+        .iter()
+        .zip(&new_sections)
+        .filter(|(old, cur)| data[old.start..][..old.length] != new[cur.start..][..cur.length])
+        .map(|(old, _)| old.name)
+        .collect();
+    let (ranges, truncated) = byte_ranges(data, &new, max_ranges);
+    Ok(RoundtripDiff {
+        identical: data == new.as_slice(),
+        original_size: data.len(),
+        new_size: new.len(),
+        changed_sections,
+        ranges,
+        truncated,
+    })
+}
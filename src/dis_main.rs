@@ -28,6 +28,7 @@ pub struct DisassemblerCli {
 pub fn disassembler_main(cli: DisassemblerCli) -> Result<()> {
     let opts = DisassemblerOptions {
         roundtrip: cli.roundtrip,
+        ..Default::default()
     };
     let parse_opts = ParserOptions {
         no_short_code_attr: cli.no_short_code_attr,
@@ -42,7 +43,7 @@ pub fn disassembler_main(cli: DisassemblerCli) -> Result<()> {
     let mut error_count = 0;
     file_input_util::read_files(&cli.input, "class", |fname, data| {
         eprintln!("disassemble {}", fname);
-        let (name, out) = match disassemble(&data, parse_opts, opts) {
+        let (name, out) = match disassemble(&data, parse_opts, opts.clone()) {
             Ok(v) => v,
             Err(err) => {
                 eprintln!("Parse error in {}: {}", fname, err.0);
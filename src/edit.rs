@@ -0,0 +1,386 @@
+// Class level edits, implemented by reassembling (possibly modified) disassembly of the class, which
+// takes care of rebuilding the constant pool.
+use std::collections::HashSet;
+
+use serde::Deserialize;
+
+use crate::krakatau_lib::classfile::attrs::{Annotation, AttrBody, Attribute, ElementValue};
+use crate::krakatau_lib::classfile::code::{Code, Frame, Instr, VType};
+use crate::krakatau_lib::classfile::cpool::Const;
+use crate::krakatau_lib::classfile::{self, parse::Class};
+use crate::krakatau_lib::{assemble, disassemble, encode_mutf8, AssemblerOptions, DisassemblerOptions, ParserOptions};
+use crate::raw_class::{RawAttr, RawClass};
+
+const PARSE_OPTS: ParserOptions = ParserOptions { no_short_code_attr: false };
+
+fn parse(data: &[u8]) -> Result<Class, String> {
+    classfile::parse(data, PARSE_OPTS).map_err(|e| format!("Class parse error: {:?}", e))
+}
+
+fn disassemble_with(data: &[u8], opts: DisassemblerOptions) -> Result<String, String> {
+    let (_, out) = disassemble(data, PARSE_OPTS, opts).map_err(|e| format!("Decompilation error: {:?}", e))?;
+    String::from_utf8(out).map_err(|e| format!("Output encoding error: {}", e))
+}
+
+fn disassemble_roundtrip(data: &[u8]) -> Result<String, String> {
+    let opts = DisassemblerOptions {
+        roundtrip: true,
+        ..Default::default()
+    };
+    disassemble_with(data, opts)
+}
+
+fn assemble_one(source: &str) -> Result<Vec<u8>, String> {
+    let mut classes = assemble(source, AssemblerOptions {}).map_err(|e| format!("Assembly error: {:?}", e))?;
+    if classes.len() != 1 {
+        return Err(format!("Expected one class, got {}", classes.len()));
+    }
+    Ok(classes.pop().unwrap().1)
+}
+
+/// Byte ranges of each `.method` ... `.end method` block in disassembler output, in classfile order.
+fn method_blocks(text: &str) -> Vec<(usize, usize)> {
+    let mut blocks = Vec::new();
+    let mut start = None;
+    let mut pos = 0;
+    for line in text.split_inclusive('\n') {
+        if line.starts_with(".method ") {
+            start = Some(pos);
+        } else if line.trim_end() == ".end method" {
+            if let Some(start) = start.take() {
+                blocks.push((start, pos + line.len()));
+            }
+        }
+        pos += line.len();
+    }
+    blocks
+}
+
+fn member_key<'a>(c: &Class<'a>, m: &classfile::parse::Field) -> (Option<&'a [u8]>, Option<&'a [u8]>) {
+    (c.cp.utf8(m.name), c.cp.utf8(m.desc))
+}
+
+fn class_attr_count(c: &Class) -> usize {
+    c.attrs.iter().filter(|a| a.name_utf.0 != b"BootstrapMethods").count()
+}
+
+/// Disassembly which only depends on what a class does: debug attributes are omitted, and constants
+/// are printed inline, so the order of the constant pool makes no difference.
+pub fn normalized_disassembly(data: &[u8]) -> Result<String, String> {
+    let opts = DisassemblerOptions {
+        strip_debug: true,
+        ..Default::default()
+    };
+    disassemble_with(data, opts)
+}
+
+/// Rebuilds a class in a canonical form, so that classes which differ only in constant pool order,
+/// attribute order or trailing data produce identical bytes. Attributes are sorted by name, and the
+/// class is reassembled from non-roundtrip disassembly, which lays out the constant pool in order of
+/// first use.
+pub fn canonicalize(data: &[u8]) -> Result<Vec<u8>, String> {
+    let (mut raw, rest) = RawClass::parse_prefix(data).map_err(|e| format!("Class parse error: {}", e))?;
+    let sorted = {
+        let class = parse(&data[..data.len() - rest.len()])?;
+        let name = |a: &RawAttr| class.cp.utf8(a.name);
+        raw.attrs.sort_by_key(name);
+        for m in raw.fields.iter_mut().chain(raw.methods.iter_mut()) {
+            m.attrs.sort_by_key(name);
+        }
+        raw.to_bytes()
+    };
+    assemble_one(&disassemble_with(&sorted, DisassemblerOptions::default())?)
+}
+
+/// Adds or replaces the SourceFile attribute, or removes it if `source_file` is empty. Unlike the
+/// other edits, this works on the raw class, so the rest of the class (including the constant pool,
+/// apart from any entries appended to it) is left byte for byte the same.
+pub fn set_source_file(data: &[u8], source_file: &str) -> Result<Vec<u8>, String> {
+    let raw = RawClass::parse(data).map_err(|e| format!("Class parse error: {}", e))?;
+    let class = parse(data)?;
+
+    let mut pool = raw.cp.to_vec();
+    let mut count = raw.cp_count;
+    let mut utf8 = |s: &[u8]| {
+        if let Some(ind) = (1..raw.cp_count).find(|&ind| class.cp.utf8(ind) == Some(s)) {
+            return Ok(ind);
+        }
+        if count == u16::MAX || s.len() > u16::MAX as usize {
+            return Err("Constant pool is full".to_string());
+        }
+        pool.push(1);
+        pool.extend_from_slice(&(s.len() as u16).to_be_bytes());
+        pool.extend_from_slice(s);
+        count += 1;
+        Ok(count - 1)
+    };
+
+    let is_source_file = |a: &RawAttr| class.cp.utf8(a.name) == Some(&b"SourceFile"[..]);
+    let value;
+    let mut attrs = raw.attrs.clone();
+    if source_file.is_empty() {
+        attrs.retain(|a| !is_source_file(a));
+    } else {
+        let name = utf8(b"SourceFile")?;
+        value = utf8(&encode_mutf8(source_file))?.to_be_bytes();
+        let attr = RawAttr { name, data: &value };
+        match attrs.iter().position(is_source_file) {
+            Some(i) => attrs[i] = attr,
+            None => attrs.push(attr),
+        }
+    }
+
+    let new = RawClass {
+        cp_count: count,
+        cp: &pool,
+        attrs,
+        ..raw
+    };
+    Ok(new.to_bytes())
+}
+
+/// Indices of the constant pool entries referred to from somewhere in a class.
+#[derive(Default)]
+struct Refs(HashSet<u16>);
+
+impl Refs {
+    fn element_value(&mut self, v: &ElementValue) {
+        use ElementValue::*;
+        match v {
+            Anno(anno) => self.annotation(anno),
+            Array(vals) => vals.iter().for_each(|v| self.element_value(v)),
+            Enum(desc, name) => self.0.extend([*desc, *name]),
+            Class(ind) | Str(ind) | Byte(ind) | Boolean(ind) | Char(ind) | Short(ind) | Int(ind) | Float(ind)
+            | Long(ind) | Double(ind) => {
+                self.0.insert(*ind);
+            }
+        }
+    }
+
+    fn annotation(&mut self, anno: &Annotation) {
+        self.0.insert(anno.0);
+        for (name, v) in &anno.1 {
+            self.0.insert(*name);
+            self.element_value(v);
+        }
+    }
+
+    fn vtypes<'v>(&mut self, vtypes: impl IntoIterator<Item = &'v VType>) {
+        for vt in vtypes {
+            if let VType::Object(ind) = vt {
+                self.0.insert(*ind);
+            }
+        }
+    }
+
+    fn code(&mut self, code: &Code) {
+        use Instr::*;
+        for (_, instr) in &code.bytecode.0 {
+            let ind = match instr {
+                Ldc(ind) => (*ind).into(),
+                LdcW(ind) | Ldc2W(ind) | Getstatic(ind) | Putstatic(ind) | Getfield(ind) | Putfield(ind) => *ind,
+                Invokevirtual(ind) | Invokespecial(ind) | Invokestatic(ind) | Invokeinterface(ind, _) => *ind,
+                Invokedynamic(ind) | New(ind) | Anewarray(ind) | Checkcast(ind) | Instanceof(ind) => *ind,
+                Multianewarray(ind, _) => *ind,
+                _ => continue,
+            };
+            self.0.insert(ind);
+        }
+        self.0.extend(code.exceptions.iter().map(|e| e.ctype));
+        self.attrs(&code.attrs);
+    }
+
+    fn attrs(&mut self, attrs: &[Attribute]) {
+        for attr in attrs {
+            self.0.insert(attr.name);
+            match &attr.body {
+                AttrBody::AnnotationDefault(v) => self.element_value(v),
+                AttrBody::BootstrapMethods(methods) => {
+                    for bsm in methods {
+                        self.0.insert(bsm.bsref);
+                        self.0.extend(&bsm.args);
+                    }
+                }
+                AttrBody::Code((code, long)) => {
+                    self.code(code);
+                    if let Some(long) = long {
+                        self.code(long);
+                    }
+                }
+                AttrBody::ConstantValue(ind)
+                | AttrBody::ModuleMainClass(ind)
+                | AttrBody::NestHost(ind)
+                | AttrBody::Signature(ind)
+                | AttrBody::SourceFile(ind) => {
+                    self.0.insert(*ind);
+                }
+                AttrBody::EnclosingMethod(cls, method) => self.0.extend([*cls, *method]),
+                AttrBody::Exceptions(inds)
+                | AttrBody::ModulePackages(inds)
+                | AttrBody::NestMembers(inds)
+                | AttrBody::PermittedSubclasses(inds) => self.0.extend(inds),
+                // The last field is the access flags
+                AttrBody::InnerClasses(lines) => {
+                    for &(inner, outer, name, _) in lines {
+                        self.0.extend([inner, outer, name]);
+                    }
+                }
+                AttrBody::LocalVariableTable(lines) | AttrBody::LocalVariableTypeTable(lines) => {
+                    self.0.extend(lines.iter().flat_map(|l| [l.name, l.desc]));
+                }
+                AttrBody::MethodParameters(params) => self.0.extend(params.iter().map(|&(name, _)| name)),
+                AttrBody::Module(m) => {
+                    self.0.extend([m.module, m.version]);
+                    self.0.extend(m.requires.iter().flat_map(|r| [r.module, r.version]));
+                    for p in m.exports.iter().chain(&m.opens) {
+                        self.0.insert(p.package);
+                        self.0.extend(&p.modules);
+                    }
+                    self.0.extend(&m.uses);
+                    for p in &m.provides {
+                        self.0.insert(p.cls);
+                        self.0.extend(&p.provides_with);
+                    }
+                }
+                AttrBody::Record(components) => {
+                    for c in components {
+                        self.0.extend([c.name, c.desc]);
+                        self.attrs(&c.attrs);
+                    }
+                }
+                AttrBody::RuntimeInvisibleAnnotations(annos) | AttrBody::RuntimeVisibleAnnotations(annos) => {
+                    annos.iter().for_each(|a| self.annotation(a));
+                }
+                AttrBody::RuntimeInvisibleParameterAnnotations(params)
+                | AttrBody::RuntimeVisibleParameterAnnotations(params) => {
+                    params.iter().flat_map(|p| &p.0).for_each(|a| self.annotation(a));
+                }
+                AttrBody::RuntimeInvisibleTypeAnnotations(annos) | AttrBody::RuntimeVisibleTypeAnnotations(annos) => {
+                    annos.iter().for_each(|a| self.annotation(&a.anno));
+                }
+                AttrBody::StackMapTable(table) => {
+                    for (_, frame) in &table.0 {
+                        match frame {
+                            Frame::Stack1(vt) | Frame::Stack1Ex(vt) => self.vtypes([vt]),
+                            Frame::Append(vts) => self.vtypes(vts),
+                            Frame::Full(locals, stack) => self.vtypes(locals.iter().chain(stack)),
+                            _ => {}
+                        }
+                    }
+                }
+                // The contents of unrecognized attributes are opaque, so anything they refer to is missed
+                _ => {}
+            }
+        }
+    }
+
+    fn new(c: &Class) -> Self {
+        let mut refs = Self::default();
+        for constant in &c.cp.0 {
+            match *constant {
+                Const::Class(ind)
+                | Const::Str(ind)
+                | Const::MethodType(ind)
+                | Const::Module(ind)
+                | Const::Package(ind)
+                | Const::MethodHandle(_, ind)
+                // The first index of a dynamic constant is into the BootstrapMethods attribute
+                | Const::Dynamic(_, ind)
+                | Const::InvokeDynamic(_, ind) => {
+                    refs.0.insert(ind);
+                }
+                Const::Field(a, b) | Const::Method(a, b) | Const::InterfaceMethod(a, b) | Const::NameAndType(a, b) => {
+                    refs.0.extend([a, b]);
+                }
+                _ => {}
+            }
+        }
+        refs.0.extend([c.this, c.super_]);
+        refs.0.extend(&c.interfaces);
+        for m in c.fields.iter().chain(&c.methods) {
+            refs.0.extend([m.name, m.desc]);
+            refs.attrs(&m.attrs);
+        }
+        refs.attrs(&c.attrs);
+        refs
+    }
+}
+
+/// Indices of constant pool entries which nothing else in the class refers to, found by walking every
+/// reference in the constant pool, the class's members and its parsed attributes. References from
+/// other constants count even if those constants are unused themselves.
+pub fn unused_constants(c: &Class) -> Vec<u16> {
+    let refs = Refs::new(c);
+    // Index 0 is unused by definition, as are the slots following Longs and Doubles
+    (1..c.cp.0.len())
+        .filter(|&ind| !matches!(c.cp.0[ind], Const::Null))
+        .map(|ind| ind as u16)
+        .filter(|ind| !refs.0.contains(ind))
+        .collect()
+}
+
+/// Replaces the method with the same name and descriptor as the single method declared in
+/// `method_source`, returning the reassembled class.
+pub fn patch_method(data: &[u8], method_source: &str) -> Result<Vec<u8>, String> {
+    let orig = parse(data)?;
+    let text = disassemble_roundtrip(data)?;
+    let blocks = method_blocks(&text);
+    if blocks.len() != orig.methods.len() {
+        return Err("Could not locate methods in disassembly".to_string());
+    }
+
+    // Assemble the new method in place of all the existing ones to find out what it declares. This
+    // keeps any constant definitions from the original class available to the new source.
+    let mut probe = String::with_capacity(text.len() + method_source.len());
+    let mut last = 0;
+    for &(start, end) in &blocks {
+        probe.push_str(&text[last..start]);
+        last = end;
+    }
+    probe.push_str(method_source);
+    probe.push('\n');
+    probe.push_str(&text[last..]);
+    let probe = assemble_one(&probe)?;
+    let probe = parse(&probe)?;
+    if probe.methods.len() != 1 || probe.fields.len() != orig.fields.len() || class_attr_count(&probe) != class_attr_count(&orig) {
+        return Err("method_source must declare exactly one method and nothing else".to_string());
+    }
+
+    let key = member_key(&probe, &probe.methods[0]);
+    let index = orig
+        .methods
+        .iter()
+        .position(|m| member_key(&orig, m) == key)
+        .ok_or_else(|| "No method with a matching name and descriptor in class".to_string())?;
+
+    let (start, end) = blocks[index];
+    let patched = format!("{}{}\n{}", &text[..start], method_source.trim_end(), &text[end..]);
+    assemble_one(&patched)
+}
+
+/// Which categories of attributes `minify` removes. Attributes needed at runtime, such as Code,
+/// StackMapTable and Exceptions, are always kept.
+#[derive(Debug, Deserialize)]
+pub struct MinifyOptions {
+    /// Line numbers, local variable tables and source file information
+    #[serde(default = "yes")]
+    pub strip_debug: bool,
+    /// Annotations which aren't retained at runtime
+    #[serde(default = "yes")]
+    pub strip_invisible_annotations: bool,
+}
+
+fn yes() -> bool {
+    true
+}
+
+/// Removes attributes which aren't needed at runtime. Since the class is reassembled from
+/// (non-roundtrip) disassembly, constants only used by the removed attributes are dropped as well.
+pub fn minify(data: &[u8], opts: &MinifyOptions) -> Result<Vec<u8>, String> {
+    let dis_opts = DisassemblerOptions {
+        strip_debug: opts.strip_debug,
+        strip_invisible_annotations: opts.strip_invisible_annotations,
+        ..Default::default()
+    };
+    assemble_one(&disassemble_with(data, dis_opts)?)
+}
@@ -0,0 +1,220 @@
+// Quick estimate of how large assembling some source will make the classes, from a single pass over
+// the text. Nothing is actually assembled, so the source isn't checked for errors either.
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+/// Operand words which are part of the assembler's syntax rather than constants
+const KEYWORDS: &[&str] = &[
+    ":",
+    "=",
+    "default",
+    "Class",
+    "Field",
+    "Method",
+    "InterfaceMethod",
+    "String",
+    "MethodType",
+    "MethodHandle",
+    "InvokeDynamic",
+    "Dynamic",
+    "getField",
+    "getStatic",
+    "putField",
+    "putStatic",
+    "invokeVirtual",
+    "invokeStatic",
+    "invokeSpecial",
+    "newInvokeSpecial",
+    "invokeInterface",
+    "boolean",
+    "byte",
+    "char",
+    "short",
+    "int",
+    "long",
+    "float",
+    "double",
+];
+
+/// Fixed parts of a class: magic, version, pool count, flags, this, super and the three list counts
+const CLASS_HEADER: usize = 24;
+/// Flags, name, descriptor and attribute count
+const MEMBER_HEADER: usize = 8;
+/// Attribute header, stack and locals, code length, and the exception and attribute counts
+const CODE_HEADER: usize = 18;
+
+#[derive(Debug, Default, Serialize)]
+pub struct SizeEstimate {
+    pub estimated_size: usize,
+    pub classes: usize,
+    pub fields: usize,
+    pub methods: usize,
+    pub instructions: usize,
+    /// Distinct constants the source appears to reference
+    pub constants: usize,
+}
+
+/// Strips a trailing comment. Semicolons inside words and string literals don't start one.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut prev = ' ';
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == ';' && prev.is_whitespace() => return &line[..i],
+            None => {}
+        }
+        prev = c;
+    }
+    line
+}
+
+fn is_number(word: &str) -> bool {
+    let digits = word.trim_start_matches(|c| c == '+' || c == '-');
+    digits.starts_with(|c: char| c.is_ascii_digit()) || digits.starts_with("Infinity") || digits.starts_with("NaN")
+}
+
+fn is_label(word: &str) -> bool {
+    word.len() > 1 && word.starts_with('L') && word[1..].bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+/// Bytes of the constant, if any, that an operand word becomes.
+fn constant_size(word: &str) -> Option<usize> {
+    // Raw references like [12] name existing constants
+    if KEYWORDS.contains(&word) || is_number(word) || is_label(word) || (word.starts_with('[') && word.ends_with(']')) {
+        return None;
+    }
+    let content = word.trim_start_matches('b');
+    let content = match content.chars().next() {
+        Some(q @ ('"' | '\'')) => content.trim_start_matches(q).trim_end_matches(q),
+        _ => word,
+    };
+    // Tag and length, followed by the (roughly) encoded text
+    Some(3 + content.len())
+}
+
+/// Bytes of the non-Utf8 constants an instruction's operands need, for each distinct operand list.
+fn reference_size(opcode: &str, operands: &[&str]) -> usize {
+    match operands.first().copied() {
+        Some(word) if is_number(word) && opcode.starts_with("ldc") => {
+            if opcode == "ldc2_w" {
+                9
+            } else {
+                5
+            }
+        }
+        // Class, NameAndType and the member reference itself
+        Some("Field" | "Method" | "InterfaceMethod") => 13,
+        Some("InvokeDynamic" | "Dynamic" | "MethodHandle") => 10,
+        // Class or String
+        Some(word) if constant_size(word).is_some() => 3,
+        _ => 0,
+    }
+}
+
+/// Size in bytes of an instruction with the given opcode and operand words.
+fn instruction_size(opcode: &str, operands: &[&str]) -> usize {
+    match opcode {
+        "invokeinterface" | "invokedynamic" | "goto_w" | "jsr_w" => 5,
+        "multianewarray" => 4,
+        // Padding, default, bounds or count, plus an entry per case
+        "tableswitch" | "lookupswitch" => 12,
+        _ if operands.is_empty() => 1,
+        _ if operands.len() == 1 && is_number(operands[0]) && opcode != "sipush" => 2,
+        _ => 3,
+    }
+}
+
+pub fn estimate(source: &str) -> Result<SizeEstimate, String> {
+    let mut res = SizeEstimate::default();
+    let mut utf8 = HashSet::new();
+    let mut references = HashSet::new();
+    let mut size = 0;
+    let mut in_code = false;
+    let mut in_switch = false;
+
+    for line in source.lines() {
+        let line = strip_comment(line).trim();
+        let words: Vec<_> = line.split_whitespace().collect();
+        let Some(&first) = words.first() else {
+            continue;
+        };
+
+        match first {
+            ".class" | ".super" | ".implements" => {
+                if first == ".class" {
+                    res.classes += 1;
+                    size += CLASS_HEADER;
+                }
+                if first == ".implements" {
+                    size += 2;
+                }
+                if let Some(&name) = words.last() {
+                    utf8.insert(name);
+                    if references.insert(format!("Class {}", name)) {
+                        size += 3;
+                    }
+                }
+            }
+            ".field" | ".method" => {
+                if first == ".field" {
+                    res.fields += 1;
+                } else {
+                    res.methods += 1;
+                }
+                size += MEMBER_HEADER;
+                // .method <flags> name : descriptor, or .field <flags> name descriptor [= value]
+                let end = words.iter().position(|&w| w == "=").unwrap_or(words.len());
+                let sig = words[1..end].iter().filter(|&&w| w != ":");
+                utf8.extend(sig.rev().take(2));
+            }
+            ".code" => {
+                in_code = true;
+                size += CODE_HEADER;
+                utf8.insert("Code");
+            }
+            ".end" => in_code &= words.get(1) != Some(&"code"),
+            ".const" | ".bootstrap" => {
+                res.constants += 1;
+                size += 3 + line.len() / 2;
+            }
+            _ if in_code && !first.starts_with('.') => {
+                // Drop any label definition, leaving the instruction
+                let words = if first.ends_with(':') { &words[1..] } else { &words[..] };
+                let Some((&opcode, operands)) = words.split_first() else {
+                    continue;
+                };
+                // Switch cases, and the entries of tables such as .linenumbertable
+                if opcode == "default" || is_number(opcode) || is_label(opcode) {
+                    if in_switch {
+                        size += 8;
+                        in_switch = opcode != "default";
+                    }
+                    continue;
+                }
+                in_switch = matches!(opcode, "tableswitch" | "lookupswitch");
+                res.instructions += 1;
+                size += instruction_size(opcode, operands);
+                let refs = reference_size(opcode, operands);
+                if refs > 0 && references.insert(operands.join(" ")) {
+                    size += refs;
+                }
+                utf8.extend(operands.iter().filter(|w| constant_size(w).is_some()));
+            }
+            _ => {}
+        }
+    }
+
+    if res.classes == 0 {
+        return Err("No .class directive in source".to_string());
+    }
+    res.constants += utf8.len() + references.len();
+    res.estimated_size = size + utf8.iter().filter_map(|w| constant_size(w)).sum::<usize>();
+    Ok(res)
+}
@@ -0,0 +1,83 @@
+// Longer explanations of common assembler errors, for users new to the syntax.
+use serde::Serialize;
+
+use crate::krakatau_lib::AssembleError;
+
+#[derive(Debug, Serialize)]
+pub struct Explanation {
+    pub category: &'static str,
+    pub explanation: String,
+    pub suggestion: String,
+}
+
+// Blocks closed by `.end <name>`
+const BLOCKS: [&str; 13] = [
+    "class",
+    "method",
+    "code",
+    "fieldattributes",
+    "annotation",
+    "runtime",
+    "innerclasses",
+    "array",
+    "linenumbertable",
+    "localvariabletable",
+    "localvariabletypetable",
+    "record",
+    "module",
+];
+
+/// Explains the first message of an assembler error, if it's one of the recognized kinds.
+pub fn explain(err: &AssembleError) -> Option<Explanation> {
+    let (msg, _) = err.locations().next()?;
+
+    if let Some(block) = msg.strip_prefix("Expected ").filter(|b| BLOCKS.contains(b)) {
+        return Some(Explanation {
+            category: "unterminated_block",
+            explanation: format!(
+                "There is an unterminated .{0} block: `.end` was found here, but it doesn't close the .{0} block that is still open.",
+                block
+            ),
+            suggestion: format!("Add `.end {}` before this line, and check that the blocks nested inside it are closed too.", block),
+        });
+    }
+
+    let (category, explanation, suggestion) = match msg {
+        "Error: Unexpected end of file" => (
+            "unterminated_block",
+            "The source ended while a block (such as .class, .method or .code) was still open.",
+            "Check that every block is closed with a matching `.end` line, ending with `.end class`.",
+        ),
+        "Unrecognized attribute directive" => (
+            "unexpected_directive",
+            "This directive isn't valid at this point. If it starts a new method or field, the previous block is probably missing its `.end` line.",
+            "Close the previous block (e.g. with `.end method`) before starting the next one.",
+        ),
+        "Expected bytecode instruction or directive." => (
+            "unknown_instruction",
+            "Inside a .code block, each line must be a bytecode instruction, a label or a code directive such as .catch or .stack.",
+            "Check the spelling of the instruction mnemonic, and that `.end code` isn't missing.",
+        ),
+        "Undefined label" => (
+            "undefined_label",
+            "This instruction jumps to (or a directive refers to) a label which isn't defined anywhere in the method.",
+            "Define the label with `<name>:` at the target instruction, or fix the spelling of the reference.",
+        ),
+        "Undefined symbolic reference" => (
+            "undefined_reference",
+            "This symbolic constant pool reference ([name]) has no matching .const or .bootstrap definition.",
+            "Add a definition such as `.const [name] = ...` at class level, or fix the spelling of the reference.",
+        ),
+        "ldc constant index must be <= 255. Try using ldc_w instead." => (
+            "ldc_index",
+            "ldc has a one byte operand, so it can only load constants in the first 256 constant pool slots.",
+            "Use ldc_w, which takes a two byte index, or move the constant to a lower slot.",
+        ),
+        _ => return None,
+    };
+    Some(Explanation {
+        category,
+        explanation: explanation.to_string(),
+        suggestion: suggestion.to_string(),
+    })
+}
@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read, Write};
+
+/// Largest buffer reserved up front for an entry, based on the size its header claims
+const MAX_SIZE_HINT: u64 = 64 * 1024;
+
+/// Limits on how much of an untrusted archive gets read.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    pub max_classes: Option<usize>,
+    /// Total size of the class entries once inflated. Unlike the class limit, exceeding this is an error.
+    pub max_total_uncompressed: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct JarClasses {
+    pub classes: Vec<(String, Vec<u8>)>,
+    /// Number of class entries in the archive, including any not read due to limits
+    pub total: usize,
+    /// Why reading stopped early, if it did
+    pub truncated: Option<String>,
+}
+
+/// Reads the `.class` entries of a jar or zip archive, in archive order.
+pub fn read_classes(data: &[u8], limits: Limits) -> Result<JarClasses, String> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(data)).map_err(|e| format!("Jar read error: {}", e))?;
+
+    let mut res = JarClasses::default();
+    let mut remaining = limits.max_total_uncompressed.unwrap_or(u64::MAX);
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i).map_err(|e| format!("Jar read error: {}", e))?;
+        let name = file.name().to_owned();
+        if !name.trim_end_matches('/').ends_with(".class") {
+            continue;
+        }
+        res.total += 1;
+        if res.truncated.is_some() {
+            continue;
+        }
+        if limits.max_classes.is_some_and(|max| res.classes.len() >= max) {
+            res.truncated = Some("class limit reached".to_string());
+            continue;
+        }
+
+        // The declared size can't be trusted, so read at most one byte past the limit to detect going over,
+        // and only use it as a capacity hint up to a size most classes fit in
+        let mut buf = Vec::with_capacity(file.size().min(remaining).min(MAX_SIZE_HINT) as usize);
+        (&mut file)
+            .take(remaining.saturating_add(1))
+            .read_to_end(&mut buf)
+            .map_err(|e| format!("Jar read error in {}: {}", name, e))?;
+        if buf.len() as u64 > remaining {
+            return Err("uncompressed size limit exceeded".to_string());
+        }
+        remaining -= buf.len() as u64;
+        res.classes.push((name, buf));
+    }
+
+    if let Some(reason) = &mut res.truncated {
+        *reason = format!("{}, {} processed of {}", reason, res.classes.len(), res.total);
+    }
+    Ok(res)
+}
+
+/// Checks a manifest attribute name against the jar spec: an alphanumeric followed by alphanumerics,
+/// '-' or '_', at most 70 bytes.
+fn check_attribute_name(name: &str) -> Result<(), String> {
+    let valid = name.len() <= 70
+        && name.bytes().next().is_some_and(|b| b.is_ascii_alphanumeric())
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid manifest attribute name {:?}", name))
+    }
+}
+
+/// Builds META-INF/MANIFEST.MF with the given main attributes. Manifest-Version always comes first,
+/// defaulting to 1.0, and lines longer than the 72 byte limit are continued on the next line.
+pub fn manifest(attrs: &BTreeMap<String, String>) -> Result<Vec<u8>, String> {
+    // Attribute names are case insensitive
+    let is_version = |name: &str| name.eq_ignore_ascii_case("Manifest-Version");
+    let version = attrs
+        .iter()
+        .find(|(name, _)| is_version(name))
+        .map_or("1.0", |(_, v)| v.as_str());
+    let rest = attrs.iter().filter(|(name, _)| !is_version(name));
+
+    let mut out = Vec::new();
+    for (name, value) in std::iter::once(("Manifest-Version", version)).chain(rest.map(|(k, v)| (&**k, &**v))) {
+        check_attribute_name(name)?;
+        if value.contains(&['\r', '\n', '\0'][..]) {
+            return Err(format!("Manifest attribute {} contains a line break or NUL", name));
+        }
+
+        let line = format!("{}: {}", name, value);
+        let mut chunk = line.as_str();
+        let mut limit = 72;
+        while chunk.len() > limit {
+            let mut end = limit;
+            while !chunk.is_char_boundary(end) {
+                end -= 1;
+            }
+            out.extend_from_slice(chunk[..end].as_bytes());
+            out.extend_from_slice(b"\r\n ");
+            chunk = &chunk[end..];
+            // Continuation lines start with a space, which counts towards the limit
+            limit = 71;
+        }
+        out.extend_from_slice(chunk.as_bytes());
+        out.extend_from_slice(b"\r\n");
+    }
+    out.extend_from_slice(b"\r\n");
+    Ok(out)
+}
+
+/// Writes a jar with the given manifest followed by the given entries, all stored uncompressed with a
+/// fixed timestamp so the output is reproducible.
+pub fn write_jar(manifest: &[u8], entries: &[(String, Vec<u8>)]) -> Result<Vec<u8>, String> {
+    let mut zw = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::FileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored)
+        .last_modified_time(zip::DateTime::default());
+
+    let mut seen = std::collections::HashSet::new();
+    let manifest_entry = ("META-INF/MANIFEST.MF", manifest);
+    for (name, data) in std::iter::once(manifest_entry).chain(entries.iter().map(|(n, d)| (n.as_str(), d.as_slice()))) {
+        if !seen.insert(name) {
+            return Err(format!("Duplicate jar entry {}", name));
+        }
+        zw.start_file(name, options).map_err(|e| format!("Jar write error: {}", e))?;
+        zw.write_all(data).map_err(|e| format!("Jar write error: {}", e))?;
+    }
+    let cursor = zw.finish().map_err(|e| format!("Jar write error: {}", e))?;
+    Ok(cursor.into_inner())
+}
@@ -18,12 +18,31 @@ use crate::krakatau_lib::disassemble::string::parse_utf8;
 use base_parser::BaseParser;
 use class_parser::ClassParser;
 pub use span::Error;
+use span::Span;
+pub use string::encode_mutf8;
+pub use string::unescape;
 use tokenize::tokenize;
+use tokenize::TokenType;
 
 #[derive(Debug, Clone, Copy)]
 pub struct AssemblerOptions {}
 
-pub fn assemble(source: &str, _opts: AssemblerOptions) -> Result<Vec<(Option<String>, Vec<u8>)>, Error> {
+pub fn assemble(source: &str, opts: AssemblerOptions) -> Result<Vec<(Option<String>, Vec<u8>)>, Error> {
+    let mut results = Vec::new();
+    assemble_each(source, opts, |class_name, data| {
+        results.push((class_name, data));
+        true
+    })?;
+    Ok(results)
+}
+
+/// Like `assemble`, but passes each class to `emit` as soon as it's assembled, rather than once the
+/// whole source is done. Stops early if `emit` returns false.
+pub fn assemble_each(
+    source: &str,
+    _opts: AssemblerOptions,
+    mut emit: impl FnMut(Option<String>, Vec<u8>) -> bool,
+) -> Result<(), Error> {
     let tokens = tokenize(source)?;
     // for tok in &tokens {
     //     println!("{:?}", tok);
@@ -31,20 +50,57 @@ pub fn assemble(source: &str, _opts: AssemblerOptions) -> Result<Vec<(Option<Str
 
     let arena = Arena::new();
     let mut base_parser = BaseParser::new(source, tokens);
-    let mut results = Vec::new();
 
     while base_parser.has_tokens_left() {
         let parser = ClassParser::new(base_parser, &arena);
         let (parser, (class_name, data)) = parser.parse()?;
         // let class_name = class_name.and_then(|bs| std::str::from_utf8(bs).ok().map(str::to_owned));
         let class_name = class_name.and_then(parse_utf8);
-        results.push((class_name, data));
 
         base_parser = parser;
         if writer::UNUSED_PH.load(std::sync::atomic::Ordering::Relaxed) {
             panic!("Unused placeholder!");
         }
+        if !emit(class_name, data) {
+            break;
+        }
     }
 
-    Ok(results)
+    Ok(())
+}
+
+/// Splits source into the exact text of each class, from the start of the line of its first directive
+/// (which may be e.g. .version rather than .class) to the end of its `.end class`, along with the class
+/// name if it's given as a plain word. This only tokenizes the source, so each class may still fail to
+/// assemble.
+pub fn split_classes(source: &str) -> Result<Vec<(Option<&str>, &str)>, Error> {
+    let tokens = tokenize(source)?;
+    let offset = |span: Span| span.0.as_ptr() as usize - source.as_ptr() as usize;
+
+    let mut blocks = Vec::new();
+    let mut start = None;
+    let mut name = None;
+    for (i, tok) in tokens.iter().enumerate() {
+        if tok.0 == TokenType::Newlines {
+            continue;
+        }
+        let block_start = *start.get_or_insert_with(|| source[..offset(tok.1)].rfind('\n').map_or(0, |p| p + 1));
+
+        if tok.0 == TokenType::Directive && tok.1 .0 == ".class" {
+            let line = tokens[i + 1..].iter().take_while(|t| t.0 != TokenType::Newlines);
+            name = line.last().filter(|t| t.0 == TokenType::Word).map(|t| t.1 .0);
+        } else if tok.0 == TokenType::Directive && tok.1 .0 == ".end" {
+            if let Some(next) = tokens.get(i + 1).filter(|t| t.1 .0 == "class") {
+                let end = offset(next.1) + next.1 .0.len();
+                blocks.push((name.take(), &source[block_start..end]));
+                start = None;
+            }
+        }
+    }
+
+    if start.is_some() {
+        let eof = tokens.last().unwrap().1;
+        return Err(Error::new(source, vec![("Error: Unexpected end of file", eof)]));
+    }
+    Ok(blocks)
 }
@@ -159,6 +159,20 @@ impl Error {
         )
     }
 
+    /// Each message along with the byte offset in the source where its span starts
+    pub fn locations(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+        self.0.iter().map(|(msg, span)| (msg.as_str(), span.start))
+    }
+
+    /// Moves every span with `f`, for errors in source which was rewritten before being assembled.
+    pub fn map_offsets(mut self, f: impl Fn(usize) -> usize) -> Self {
+        for (_, span) in &mut self.0 {
+            span.start = f(span.start);
+            span.end = f(span.end).max(span.start);
+        }
+        self
+    }
+
     pub fn display(&self, fname: &str, source: &str) {
         let printer = ErrorPrinter::new(fname, source);
         let mut is_first = true;
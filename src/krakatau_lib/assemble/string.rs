@@ -26,6 +26,15 @@ fn mutf8_char(out: &mut Vec<u8>, c: char) {
     }
 }
 
+/// Encodes a string as Modified UTF-8, as used by Utf8 constants.
+pub fn encode_mutf8(s: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        mutf8_char(&mut out, c);
+    }
+    out
+}
+
 pub fn unescape(s: &str) -> Result<Vec<u8>, (&'static str, &str)> {
     let mut out = Vec::with_capacity(s.len() - 2);
 
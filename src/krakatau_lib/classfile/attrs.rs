@@ -277,6 +277,53 @@ impl LocalVarLine {
 }
 ///////////////////////////////////////////////////////////////////////////////
 
+/// Attributes which `AttrBody` decodes, and which thus round trip through dedicated assembler directives.
+/// Keep in sync with `try_parse`. Any other attribute, or one of these which fails to parse, is kept as
+/// opaque bytes.
+pub const PARSED_ATTRIBUTES: [&str; 30] = [
+    "AnnotationDefault",
+    "BootstrapMethods",
+    "Code",
+    "ConstantValue",
+    "Deprecated",
+    "EnclosingMethod",
+    "Exceptions",
+    "InnerClasses",
+    "LineNumberTable",
+    "LocalVariableTable",
+    "LocalVariableTypeTable",
+    "MethodParameters",
+    "Module",
+    "ModuleMainClass",
+    "ModulePackages",
+    "NestHost",
+    "NestMembers",
+    "PermittedSubclasses",
+    "Record",
+    "RuntimeInvisibleAnnotations",
+    "RuntimeInvisibleParameterAnnotations",
+    "RuntimeInvisibleTypeAnnotations",
+    "RuntimeVisibleAnnotations",
+    "RuntimeVisibleParameterAnnotations",
+    "RuntimeVisibleTypeAnnotations",
+    "Signature",
+    "SourceDebugExtension",
+    "SourceFile",
+    "StackMapTable",
+    "Synthetic",
+];
+
+/// Attributes written by common compilers and the JDK which aren't decoded, and so only round trip
+/// as opaque bytes.
+pub const OPAQUE_ATTRIBUTES: [&str; 6] = [
+    "CharacterRangeTable",
+    "CompilationID",
+    "ModuleHashes",
+    "ModuleResolution",
+    "ModuleTarget",
+    "SourceID",
+];
+
 #[derive(Debug)]
 pub enum AttrBody<'a> {
     AnnotationDefault(Box<ElementValue>),
@@ -400,7 +447,6 @@ pub struct Attribute<'a> {
     pub name: u16,
     pub length: u32,
     pub actual_length: u32,
-    #[allow(dead_code)]
     pub name_utf: BStr<'a>,
     pub body: AttrBody<'a>,
 }
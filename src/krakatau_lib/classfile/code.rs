@@ -14,6 +14,10 @@ pub struct CodeOptions {
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
 pub struct Pos(u32);
 impl Pos {
+    pub fn offset(self) -> u32 {
+        self.0
+    }
+
     fn off_sub(self, off: i32) -> Result<Self, ParseError> {
         match (self.0 as i64)
             .checked_add(off as i64)
@@ -214,6 +218,212 @@ impl Display for NewArrayTag {
     }
 }
 
+/// Mnemonic of each opcode, indexed by opcode
+pub const MNEMONICS: [&str; 0xCA] = [
+    "nop",
+    "aconst_null",
+    "iconst_m1",
+    "iconst_0",
+    "iconst_1",
+    "iconst_2",
+    "iconst_3",
+    "iconst_4",
+    "iconst_5",
+    "lconst_0",
+    "lconst_1",
+    "fconst_0",
+    "fconst_1",
+    "fconst_2",
+    "dconst_0",
+    "dconst_1",
+    "bipush",
+    "sipush",
+    "ldc",
+    "ldc_w",
+    "ldc2_w",
+    "iload",
+    "lload",
+    "fload",
+    "dload",
+    "aload",
+    "iload_0",
+    "iload_1",
+    "iload_2",
+    "iload_3",
+    "lload_0",
+    "lload_1",
+    "lload_2",
+    "lload_3",
+    "fload_0",
+    "fload_1",
+    "fload_2",
+    "fload_3",
+    "dload_0",
+    "dload_1",
+    "dload_2",
+    "dload_3",
+    "aload_0",
+    "aload_1",
+    "aload_2",
+    "aload_3",
+    "iaload",
+    "laload",
+    "faload",
+    "daload",
+    "aaload",
+    "baload",
+    "caload",
+    "saload",
+    "istore",
+    "lstore",
+    "fstore",
+    "dstore",
+    "astore",
+    "istore_0",
+    "istore_1",
+    "istore_2",
+    "istore_3",
+    "lstore_0",
+    "lstore_1",
+    "lstore_2",
+    "lstore_3",
+    "fstore_0",
+    "fstore_1",
+    "fstore_2",
+    "fstore_3",
+    "dstore_0",
+    "dstore_1",
+    "dstore_2",
+    "dstore_3",
+    "astore_0",
+    "astore_1",
+    "astore_2",
+    "astore_3",
+    "iastore",
+    "lastore",
+    "fastore",
+    "dastore",
+    "aastore",
+    "bastore",
+    "castore",
+    "sastore",
+    "pop",
+    "pop2",
+    "dup",
+    "dup_x1",
+    "dup_x2",
+    "dup2",
+    "dup2_x1",
+    "dup2_x2",
+    "swap",
+    "iadd",
+    "ladd",
+    "fadd",
+    "dadd",
+    "isub",
+    "lsub",
+    "fsub",
+    "dsub",
+    "imul",
+    "lmul",
+    "fmul",
+    "dmul",
+    "idiv",
+    "ldiv",
+    "fdiv",
+    "ddiv",
+    "irem",
+    "lrem",
+    "frem",
+    "drem",
+    "ineg",
+    "lneg",
+    "fneg",
+    "dneg",
+    "ishl",
+    "lshl",
+    "ishr",
+    "lshr",
+    "iushr",
+    "lushr",
+    "iand",
+    "land",
+    "ior",
+    "lor",
+    "ixor",
+    "lxor",
+    "iinc",
+    "i2l",
+    "i2f",
+    "i2d",
+    "l2i",
+    "l2f",
+    "l2d",
+    "f2i",
+    "f2l",
+    "f2d",
+    "d2i",
+    "d2l",
+    "d2f",
+    "i2b",
+    "i2c",
+    "i2s",
+    "lcmp",
+    "fcmpl",
+    "fcmpg",
+    "dcmpl",
+    "dcmpg",
+    "ifeq",
+    "ifne",
+    "iflt",
+    "ifge",
+    "ifgt",
+    "ifle",
+    "if_icmpeq",
+    "if_icmpne",
+    "if_icmplt",
+    "if_icmpge",
+    "if_icmpgt",
+    "if_icmple",
+    "if_acmpeq",
+    "if_acmpne",
+    "goto",
+    "jsr",
+    "ret",
+    "tableswitch",
+    "lookupswitch",
+    "ireturn",
+    "lreturn",
+    "freturn",
+    "dreturn",
+    "areturn",
+    "return",
+    "getstatic",
+    "putstatic",
+    "getfield",
+    "putfield",
+    "invokevirtual",
+    "invokespecial",
+    "invokestatic",
+    "invokeinterface",
+    "invokedynamic",
+    "new",
+    "newarray",
+    "anewarray",
+    "arraylength",
+    "athrow",
+    "checkcast",
+    "instanceof",
+    "monitorenter",
+    "monitorexit",
+    "wide",
+    "multianewarray",
+    "ifnull",
+    "ifnonnull",
+    "goto_w",
+    "jsr_w",
+];
+
 #[derive(Debug)]
 pub enum WideInstr {
     Iload(u16),
@@ -250,6 +460,29 @@ impl WideInstr {
             _ => return ParseError::s("Invalid wide instr opcode"),
         })
     }
+
+    /// Opcode of the instruction which the wide prefix modifies
+    pub fn opcode(&self) -> u8 {
+        use WideInstr::*;
+        match self {
+            Iload(..) => 0x15,
+            Lload(..) => 0x16,
+            Fload(..) => 0x17,
+            Dload(..) => 0x18,
+            Aload(..) => 0x19,
+            Istore(..) => 0x36,
+            Lstore(..) => 0x37,
+            Fstore(..) => 0x38,
+            Dstore(..) => 0x39,
+            Astore(..) => 0x3A,
+            Iinc(..) => 0x84,
+            Ret(..) => 0xA9,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        MNEMONICS[self.opcode() as usize]
+    }
 }
 
 #[derive(Debug)]
@@ -669,6 +902,219 @@ impl Instr {
         })
     }
 
+    /// The opcode byte, which is 0xC4 for every wide instruction
+    pub fn opcode(&self) -> u8 {
+        use Instr::*;
+        match self {
+            Nop => 0x00,
+            AconstNull => 0x01,
+            IconstM1 => 0x02,
+            Iconst0 => 0x03,
+            Iconst1 => 0x04,
+            Iconst2 => 0x05,
+            Iconst3 => 0x06,
+            Iconst4 => 0x07,
+            Iconst5 => 0x08,
+            Lconst0 => 0x09,
+            Lconst1 => 0x0A,
+            Fconst0 => 0x0B,
+            Fconst1 => 0x0C,
+            Fconst2 => 0x0D,
+            Dconst0 => 0x0E,
+            Dconst1 => 0x0F,
+            Bipush(..) => 0x10,
+            Sipush(..) => 0x11,
+            Ldc(..) => 0x12,
+            LdcW(..) => 0x13,
+            Ldc2W(..) => 0x14,
+            Iload(..) => 0x15,
+            Lload(..) => 0x16,
+            Fload(..) => 0x17,
+            Dload(..) => 0x18,
+            Aload(..) => 0x19,
+            Iload0 => 0x1A,
+            Iload1 => 0x1B,
+            Iload2 => 0x1C,
+            Iload3 => 0x1D,
+            Lload0 => 0x1E,
+            Lload1 => 0x1F,
+            Lload2 => 0x20,
+            Lload3 => 0x21,
+            Fload0 => 0x22,
+            Fload1 => 0x23,
+            Fload2 => 0x24,
+            Fload3 => 0x25,
+            Dload0 => 0x26,
+            Dload1 => 0x27,
+            Dload2 => 0x28,
+            Dload3 => 0x29,
+            Aload0 => 0x2A,
+            Aload1 => 0x2B,
+            Aload2 => 0x2C,
+            Aload3 => 0x2D,
+            Iaload => 0x2E,
+            Laload => 0x2F,
+            Faload => 0x30,
+            Daload => 0x31,
+            Aaload => 0x32,
+            Baload => 0x33,
+            Caload => 0x34,
+            Saload => 0x35,
+            Istore(..) => 0x36,
+            Lstore(..) => 0x37,
+            Fstore(..) => 0x38,
+            Dstore(..) => 0x39,
+            Astore(..) => 0x3A,
+            Istore0 => 0x3B,
+            Istore1 => 0x3C,
+            Istore2 => 0x3D,
+            Istore3 => 0x3E,
+            Lstore0 => 0x3F,
+            Lstore1 => 0x40,
+            Lstore2 => 0x41,
+            Lstore3 => 0x42,
+            Fstore0 => 0x43,
+            Fstore1 => 0x44,
+            Fstore2 => 0x45,
+            Fstore3 => 0x46,
+            Dstore0 => 0x47,
+            Dstore1 => 0x48,
+            Dstore2 => 0x49,
+            Dstore3 => 0x4A,
+            Astore0 => 0x4B,
+            Astore1 => 0x4C,
+            Astore2 => 0x4D,
+            Astore3 => 0x4E,
+            Iastore => 0x4F,
+            Lastore => 0x50,
+            Fastore => 0x51,
+            Dastore => 0x52,
+            Aastore => 0x53,
+            Bastore => 0x54,
+            Castore => 0x55,
+            Sastore => 0x56,
+            Pop => 0x57,
+            Pop2 => 0x58,
+            Dup => 0x59,
+            DupX1 => 0x5A,
+            DupX2 => 0x5B,
+            Dup2 => 0x5C,
+            Dup2X1 => 0x5D,
+            Dup2X2 => 0x5E,
+            Swap => 0x5F,
+            Iadd => 0x60,
+            Ladd => 0x61,
+            Fadd => 0x62,
+            Dadd => 0x63,
+            Isub => 0x64,
+            Lsub => 0x65,
+            Fsub => 0x66,
+            Dsub => 0x67,
+            Imul => 0x68,
+            Lmul => 0x69,
+            Fmul => 0x6A,
+            Dmul => 0x6B,
+            Idiv => 0x6C,
+            Ldiv => 0x6D,
+            Fdiv => 0x6E,
+            Ddiv => 0x6F,
+            Irem => 0x70,
+            Lrem => 0x71,
+            Frem => 0x72,
+            Drem => 0x73,
+            Ineg => 0x74,
+            Lneg => 0x75,
+            Fneg => 0x76,
+            Dneg => 0x77,
+            Ishl => 0x78,
+            Lshl => 0x79,
+            Ishr => 0x7A,
+            Lshr => 0x7B,
+            Iushr => 0x7C,
+            Lushr => 0x7D,
+            Iand => 0x7E,
+            Land => 0x7F,
+            Ior => 0x80,
+            Lor => 0x81,
+            Ixor => 0x82,
+            Lxor => 0x83,
+            Iinc(..) => 0x84,
+            I2l => 0x85,
+            I2f => 0x86,
+            I2d => 0x87,
+            L2i => 0x88,
+            L2f => 0x89,
+            L2d => 0x8A,
+            F2i => 0x8B,
+            F2l => 0x8C,
+            F2d => 0x8D,
+            D2i => 0x8E,
+            D2l => 0x8F,
+            D2f => 0x90,
+            I2b => 0x91,
+            I2c => 0x92,
+            I2s => 0x93,
+            Lcmp => 0x94,
+            Fcmpl => 0x95,
+            Fcmpg => 0x96,
+            Dcmpl => 0x97,
+            Dcmpg => 0x98,
+            Ifeq(..) => 0x99,
+            Ifne(..) => 0x9A,
+            Iflt(..) => 0x9B,
+            Ifge(..) => 0x9C,
+            Ifgt(..) => 0x9D,
+            Ifle(..) => 0x9E,
+            IfIcmpeq(..) => 0x9F,
+            IfIcmpne(..) => 0xA0,
+            IfIcmplt(..) => 0xA1,
+            IfIcmpge(..) => 0xA2,
+            IfIcmpgt(..) => 0xA3,
+            IfIcmple(..) => 0xA4,
+            IfAcmpeq(..) => 0xA5,
+            IfAcmpne(..) => 0xA6,
+            Goto(..) => 0xA7,
+            Jsr(..) => 0xA8,
+            Ret(..) => 0xA9,
+            Tableswitch(..) => 0xAA,
+            Lookupswitch(..) => 0xAB,
+            Ireturn => 0xAC,
+            Lreturn => 0xAD,
+            Freturn => 0xAE,
+            Dreturn => 0xAF,
+            Areturn => 0xB0,
+            Return => 0xB1,
+            Getstatic(..) => 0xB2,
+            Putstatic(..) => 0xB3,
+            Getfield(..) => 0xB4,
+            Putfield(..) => 0xB5,
+            Invokevirtual(..) => 0xB6,
+            Invokespecial(..) => 0xB7,
+            Invokestatic(..) => 0xB8,
+            Invokeinterface(..) => 0xB9,
+            Invokedynamic(..) => 0xBA,
+            New(..) => 0xBB,
+            Newarray(..) => 0xBC,
+            Anewarray(..) => 0xBD,
+            Arraylength => 0xBE,
+            Athrow => 0xBF,
+            Checkcast(..) => 0xC0,
+            Instanceof(..) => 0xC1,
+            Monitorenter => 0xC2,
+            Monitorexit => 0xC3,
+            Wide(..) => 0xC4,
+            Multianewarray(..) => 0xC5,
+            Ifnull(..) => 0xC6,
+            Ifnonnull(..) => 0xC7,
+            GotoW(..) => 0xC8,
+            JsrW(..) => 0xC9,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        MNEMONICS[self.opcode() as usize]
+    }
+
     fn validate(&self, pset: &PosSet, switches: &SwitchArena) -> Result<(), ParseError> {
         use Instr::*;
         match self {
@@ -1,6 +1,8 @@
 use super::flags::Flags;
+use super::refprinter::hex_int;
 use super::refprinter::RefPrinter;
 use super::refprinter::SingleTag;
+use super::string::escape;
 use super::string::escape_byte_string;
 use crate::krakatau_lib::classfile::attrs;
 use crate::krakatau_lib::classfile::attrs::AttrBody;
@@ -25,25 +27,86 @@ static SHORT_CODE_WARNING: [&str; 6] = [
     "of the code instead.",
 ];
 
-#[derive(Debug, Clone, Copy)]
+const ACC_INTERFACE: u16 = 0x0200;
+
+#[derive(Debug, Clone, Default)]
 pub struct DisassemblerOptions {
+    /// Define every constant pool entry and bootstrap method explicitly by index, so that the
+    /// output reassembles to an identical class, pool order included
     pub roundtrip: bool,
+    /// Comment each invokedynamic with its bootstrap method (and lambda implementation, if any)
+    pub inline_indy_targets: bool,
+    /// Precede each attribute with a comment giving its name and length
+    pub annotate_attributes: bool,
+    /// Comment each ldc of a method handle or method type with what it refers to
+    pub humanize_method_handles: bool,
+    /// In interfaces, print abstract methods, then other instance methods, then static methods, each
+    /// group under a comment. Ignored with `roundtrip`, since it reorders the methods.
+    pub group_interface_methods: bool,
+    /// Omit line numbers, local variable tables and source file information
+    pub strip_debug: bool,
+    /// Omit annotations which aren't retained at runtime
+    pub strip_invisible_annotations: bool,
+    pub utf8_policy: Utf8Policy,
+    /// Print integer and long constants (including bipush and sipush operands) in hexadecimal
+    pub hex_integers: bool,
+    /// Starts the comments added by the options above, instead of the assembler's `;`
+    pub annotation_comment_prefix: Option<String>,
+}
+
+impl DisassemblerOptions {
+    fn comment_prefix(&self) -> &str {
+        self.annotation_comment_prefix.as_deref().unwrap_or(";")
+    }
+
+    /// Whether the methods of a class with the given access flags are printed out of classfile order
+    pub fn groups_methods(&self, class_access: u16) -> bool {
+        self.group_interface_methods && !self.roundtrip && class_access & ACC_INTERFACE != 0
+    }
+}
+
+/// How to handle Utf8 constants which aren't valid Modified UTF-8
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Utf8Policy {
+    /// Fail with an error
+    Strict,
+    /// Substitute U+FFFD for the invalid bytes
+    Replace,
+    /// Print as a byte string, preserving the exact bytes
+    #[default]
+    RawEscape,
 }
 
+const DEBUG_ATTRS: [&[u8]; 5] = [
+    b"LineNumberTable",
+    b"LocalVariableTable",
+    b"LocalVariableTypeTable",
+    b"SourceFile",
+    b"SourceDebugExtension",
+];
+
+const INVISIBLE_ANNOTATION_ATTRS: [&[u8]; 3] = [
+    b"RuntimeInvisibleAnnotations",
+    b"RuntimeInvisibleParameterAnnotations",
+    b"RuntimeInvisibleTypeAnnotations",
+];
+
 struct Disassembler<'a, W: Write> {
     w: W,
     rp: &'a RefPrinter<'a>,
     roundtrip: bool,
+    opts: DisassemblerOptions,
     cf_version: (u16, u16),
     indentlevel: usize,
     sol: &'static str,
 }
 impl<'a, W: Write> Disassembler<'a, W> {
-    fn new(w: W, rp: &'a RefPrinter<'a>, roundtrip: bool, cf_version: (u16, u16)) -> Self {
+    fn new(w: W, rp: &'a RefPrinter<'a>, opts: DisassemblerOptions, cf_version: (u16, u16)) -> Self {
         Self {
             w,
             rp,
-            roundtrip,
+            roundtrip: opts.roundtrip,
+            opts,
             cf_version,
             indentlevel: 0,
             sol: "",
@@ -125,6 +188,16 @@ impl<'a, W: Write> Disassembler<'a, W> {
     fn attr(&mut self, a: &Attribute<'a>) -> Result<()> {
         let rp = self.rp;
 
+        if self.opts.strip_debug && DEBUG_ATTRS.contains(&a.name_utf.0) {
+            return Ok(());
+        }
+        if self.opts.strip_invisible_annotations && INVISIBLE_ANNOTATION_ATTRS.contains(&a.name_utf.0) {
+            return Ok(());
+        }
+        if self.opts.annotate_attributes {
+            let prefix = self.opts.comment_prefix();
+            writeln!(self.w, "{}{} {}, {} bytes", self.sol, prefix, escape(a.name_utf.0).1, a.length)?;
+        }
         write!(self.w, "{}", self.sol)?;
         if a.length != a.actual_length {
             write!(self.w, ".attribute {} length {} ", rp.utf(a.name), a.length)?;
@@ -377,10 +450,18 @@ impl<'a, W: Write> Disassembler<'a, W> {
             Fconst2 => writeln!(self.w, "fconst_2")?,
             Dconst0 => writeln!(self.w, "dconst_0")?,
             Dconst1 => writeln!(self.w, "dconst_1")?,
+            Bipush(v0) if self.opts.hex_integers => writeln!(self.w, "bipush {}", hex_int(*v0 as i64))?,
+            Sipush(v0) if self.opts.hex_integers => writeln!(self.w, "sipush {}", hex_int(*v0 as i64))?,
             Bipush(v0) => writeln!(self.w, "bipush {}", *v0)?,
             Sipush(v0) => writeln!(self.w, "sipush {}", *v0)?,
-            Ldc(v0) => writeln!(self.w, "ldc {}", rp.ldc(*v0 as u16))?,
-            LdcW(v0) => writeln!(self.w, "ldc_w {}", rp.ldc(*v0))?,
+            Ldc(v0) => {
+                write!(self.w, "ldc {}", rp.ldc(*v0 as u16))?;
+                self.ldc_comment(*v0 as u16)?;
+            }
+            LdcW(v0) => {
+                write!(self.w, "ldc_w {}", rp.ldc(*v0))?;
+                self.ldc_comment(*v0)?;
+            }
             Ldc2W(v0) => writeln!(self.w, "ldc2_w {}", rp.ldc(*v0))?,
             Iload(v0) => writeln!(self.w, "iload {}", *v0)?,
             Lload(v0) => writeln!(self.w, "lload {}", *v0)?,
@@ -565,7 +646,15 @@ impl<'a, W: Write> Disassembler<'a, W> {
             Invokespecial(v0) => writeln!(self.w, "invokespecial {}", rp.tagged_fmim(*v0))?,
             Invokestatic(v0) => writeln!(self.w, "invokestatic {}", rp.tagged_fmim(*v0))?,
             Invokeinterface(v0, v1) => writeln!(self.w, "invokeinterface {} {}", rp.tagged_fmim(*v0), *v1)?,
-            Invokedynamic(v0) => writeln!(self.w, "invokedynamic {}", rp.cpref(*v0))?,
+            Invokedynamic(v0) => {
+                write!(self.w, "invokedynamic {}", rp.cpref(*v0))?;
+                if self.opts.inline_indy_targets {
+                    if let Some(target) = rp.indy_target(*v0) {
+                        write!(self.w, " {} {}", self.opts.comment_prefix(), target)?;
+                    }
+                }
+                writeln!(self.w, "")?
+            }
             New(v0) => writeln!(self.w, "new {}", rp.cls(*v0))?,
             Newarray(c) => writeln!(self.w, "newarray {}", *c)?,
             Anewarray(v0) => writeln!(self.w, "anewarray {}", rp.cls(*v0))?,
@@ -601,6 +690,16 @@ impl<'a, W: Write> Disassembler<'a, W> {
         Ok(())
     }
 
+    /// Ends the line of an ldc instruction, describing the constant first if requested.
+    fn ldc_comment(&mut self, ind: u16) -> Result<()> {
+        if self.opts.humanize_method_handles {
+            if let Some(desc) = self.rp.method_handle_description(ind) {
+                write!(self.w, " {} {}", self.opts.comment_prefix(), desc)?;
+            }
+        }
+        writeln!(self.w, "")
+    }
+
     fn code(&mut self, c: &code::Code<'a>) -> Result<()> {
         let mut stack_map_table = None;
         let mut skip = std::ptr::null();
@@ -872,7 +971,7 @@ impl<'a, W: Write> Disassembler<'a, W> {
     }
 }
 
-pub fn disassemble(mut w: impl Write, c: &Class, opts: DisassemblerOptions) -> Result<()> {
+fn ref_printer<'a>(c: &'a Class, opts: &DisassemblerOptions) -> RefPrinter<'a> {
     let mut bstable = None;
     let mut inner_classes = None;
     for attr in &c.attrs {
@@ -884,7 +983,38 @@ pub fn disassemble(mut w: impl Write, c: &Class, opts: DisassemblerOptions) -> R
         }
     }
 
-    let rp = RefPrinter::new(opts.roundtrip, &c.cp, bstable, inner_classes);
+    let replace_invalid_utf8 = opts.utf8_policy == Utf8Policy::Replace;
+    RefPrinter::new(opts.roundtrip, replace_invalid_utf8, opts.hex_integers, &c.cp, bstable, inner_classes)
+}
+
+/// Disassembles each instruction of a method separately, as (bytecode offset, mnemonic, operands).
+/// Returns None if the method has no code.
+pub fn instructions(c: &Class, m: &Field, opts: DisassemblerOptions) -> Result<Option<Vec<(u32, String, String)>>> {
+    let code = m.attrs.iter().find_map(|a| match &a.body {
+        AttrBody::Code((code, _)) => Some(code),
+        _ => None,
+    });
+    let code = match code {
+        Some(code) => code,
+        None => return Ok(None),
+    };
+
+    let rp = ref_printer(c, &opts);
+    let mut d = Disassembler::new(Vec::new(), &rp, opts, c.version);
+    let mut res = Vec::with_capacity(code.bytecode.0.len());
+    for &(pos, ref instr) in code.bytecode.0.iter() {
+        d.w.clear();
+        d.instr(instr, &code.bytecode.2)?;
+        let text = String::from_utf8_lossy(&d.w);
+        let text = text.trim_end();
+        let (op, operands) = text.split_once(' ').unwrap_or((text, ""));
+        res.push((pos.offset(), op.to_owned(), operands.to_owned()));
+    }
+    Ok(Some(res))
+}
+
+pub fn disassemble(mut w: impl Write, c: &Class, opts: DisassemblerOptions) -> Result<()> {
+    let rp = ref_printer(c, &opts);
 
     // d.v(".version")?.v(c.version.0)?.v(c.version.1)?.eol()?;
 
@@ -902,13 +1032,33 @@ pub fn disassemble(mut w: impl Write, c: &Class, opts: DisassemblerOptions) -> R
         writeln!(w, ".implements {}", rp.cls(ind))?;
     }
 
-    let mut d = Disassembler::new(w, &rp, opts.roundtrip, c.version);
+    let group_methods = opts.groups_methods(c.access);
+    let mut d = Disassembler::new(w, &rp, opts, c.version);
     for field in c.fields.iter() {
         d.field(field)?;
     }
 
-    for method in c.methods.iter() {
-        d.method(method)?;
+    if group_methods {
+        let group = |access: u16| match (access & 0x0400 != 0, access & 0x0008 != 0) {
+            (true, _) => 0,
+            (false, false) => 1,
+            (false, true) => 2,
+        };
+        let banners = ["Abstract methods", "Default and private methods", "Static methods"];
+        for (i, banner) in banners.into_iter().enumerate() {
+            let methods: Vec<_> = c.methods.iter().filter(|m| group(m.access) == i).collect();
+            if methods.is_empty() {
+                continue;
+            }
+            writeln!(d.w, "\n{} {}", d.opts.comment_prefix(), banner)?;
+            for method in methods {
+                d.method(method)?;
+            }
+        }
+    } else {
+        for method in c.methods.iter() {
+            d.method(method)?;
+        }
     }
 
     for attr in c.attrs.iter() {
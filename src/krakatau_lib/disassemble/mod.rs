@@ -4,4 +4,6 @@ mod refprinter;
 pub mod string;
 
 pub use disassembler::disassemble;
+pub use disassembler::instructions;
 pub use disassembler::DisassemblerOptions;
+pub use disassembler::Utf8Policy;
@@ -4,6 +4,7 @@ use std::fmt;
 use std::fmt::Display;
 
 use super::string::escape;
+use super::string::escape_lossy;
 use super::string::StrLitType;
 use crate::krakatau_lib::classfile::attrs::BootstrapMethod;
 use crate::krakatau_lib::classfile::cpool::Const;
@@ -98,6 +99,16 @@ enum DynTag {
     InvokeDynamic,
 }
 
+/// Hexadecimal with an explicit sign rather than two's complement, so it's unambiguous regardless of
+/// the width of the type, e.g. -0x1 rather than 0xFFFFFFFF.
+pub(super) fn hex_int(v: i64) -> String {
+    if v < 0 {
+        format!("-0x{:X}", v.unsigned_abs())
+    } else {
+        format!("0x{:X}", v)
+    }
+}
+
 enum ConstData<'a> {
     Invalid,
     Utf8(UtfData<'a>),
@@ -110,12 +121,12 @@ enum ConstData<'a> {
     Dyn(DynTag, u16, u16),
 }
 impl<'a> ConstData<'a> {
-    fn new(roundtrip: bool, c: &Const<'a>) -> Self {
+    fn new(roundtrip: bool, replace_invalid_utf8: bool, hex_integers: bool, c: &Const<'a>) -> Self {
         use Const::*;
         match c {
             Null => ConstData::Invalid,
             Utf8(s) => {
-                let (stype, s) = escape(s.0);
+                let (stype, s) = if replace_invalid_utf8 { escape_lossy(s.0) } else { escape(s.0) };
                 ConstData::Utf8(UtfData {
                     stype,
                     s,
@@ -123,6 +134,8 @@ impl<'a> ConstData<'a> {
                 })
             }
 
+            Int(v) if hex_integers => ConstData::Prim(PrimTag::Int, hex_int(*v as i32 as i64)),
+            Long(v) if hex_integers => ConstData::Prim(PrimTag::Long, format!("{}L", hex_int(*v as i64))),
             Int(v) => ConstData::Prim(PrimTag::Int, format!("{}", *v as i32)),
             Long(v) => ConstData::Prim(PrimTag::Long, format!("{}L", *v as i64)),
             Float(v) => ConstData::Prim(PrimTag::Float, {
@@ -180,9 +193,9 @@ struct ConstLine<'a> {
     sym_used: Cell<bool>,
 }
 impl<'a> ConstLine<'a> {
-    fn new(roundtrip: bool, c: &Const<'a>) -> Self {
+    fn new(roundtrip: bool, replace_invalid_utf8: bool, hex_integers: bool, c: &Const<'a>) -> Self {
         Self {
-            data: ConstData::new(roundtrip, c),
+            data: ConstData::new(roundtrip, replace_invalid_utf8, hex_integers, c),
             force_raw: roundtrip,
             is_defined: Cell::new(false),
             sym_used: Cell::new(false),
@@ -198,13 +211,19 @@ pub(super) struct RefPrinter<'a> {
 impl<'a> RefPrinter<'a> {
     pub(super) fn new(
         roundtrip: bool,
+        replace_invalid_utf8: bool,
+        hex_integers: bool,
         cp: &ConstPool<'a>,
         bs: Option<&'a [BootstrapMethod]>,
         inner_classes: Option<&'a [(u16, u16, u16, u16)]>,
     ) -> Self {
         let mut new = Self {
             roundtrip,
-            cpool: cp.0.iter().map(|c| ConstLine::new(roundtrip, c)).collect(),
+            cpool: cp
+                .0
+                .iter()
+                .map(|c| ConstLine::new(roundtrip, replace_invalid_utf8, hex_integers, c))
+                .collect(),
             bs: bs.unwrap_or(&[]),
         };
 
@@ -391,6 +410,83 @@ impl<'a> RefPrinter<'a> {
         })
     }
 
+    fn raw_data(&self, ind: u16) -> Option<&ConstData<'a>> {
+        self.cpool.get(ind as usize).map(|line| &line.data)
+    }
+
+    fn utf_lit(&self, ind: u16) -> Option<StringLit<'_>> {
+        if let Some(ConstData::Utf8(d)) = self.raw_data(ind) {
+            Some(d.to_lit())
+        } else {
+            None
+        }
+    }
+
+    fn raw_utf(&self, ind: u16) -> Option<&str> {
+        self.utf_lit(ind).map(|lit| lit.s)
+    }
+
+    // Resolves a member ref to (class, name, descriptor) literals, regardless of whether it would be printed raw
+    fn member_lits(&self, ind: u16) -> Option<(StringLit<'_>, StringLit<'_>, StringLit<'_>)> {
+        if let Some(ConstData::Fmim(_, c, nat)) = self.raw_data(ind) {
+            if let Some(ConstData::Single(SingleTag::Class, cls)) = self.raw_data(*c) {
+                if let Some(ConstData::Nat(n, t)) = self.raw_data(*nat) {
+                    return Some((self.utf_lit(*cls)?, self.utf_lit(*n)?, self.utf_lit(*t)?));
+                }
+            }
+        }
+        None
+    }
+
+    fn member_parts(&self, ind: u16) -> Option<(&str, &str, &str)> {
+        self.member_lits(ind).map(|(cls, name, desc)| (cls.s, name.s, desc.s))
+    }
+
+    fn method_handle_tag(mhtag: u8) -> &'static str {
+        MHTAGS.get(mhtag as usize).copied().unwrap_or("INVALID")
+    }
+
+    fn method_handle_parts(&self, ind: u16) -> Option<(&'static str, (&str, &str, &str))> {
+        if let Some(ConstData::MethodHandle(mhtag, r)) = self.raw_data(ind) {
+            Some((Self::method_handle_tag(*mhtag), self.member_parts(*r)?))
+        } else {
+            None
+        }
+    }
+
+    /// Readable description of a MethodHandle or MethodType constant, such as
+    /// `REF_invokeVirtual java/lang/String.length()I`. Intended for comments only. Names are printed
+    /// as string literals where needed, so they can't break out of the comment.
+    pub(super) fn method_handle_description(&self, ind: u16) -> Option<String> {
+        match self.raw_data(ind)? {
+            ConstData::MethodHandle(mhtag, r) => {
+                let (cls, name, desc) = self.member_lits(*r)?;
+                Some(format!("REF_{} {}.{}{}", Self::method_handle_tag(*mhtag), cls, name, desc))
+            }
+            ConstData::Single(SingleTag::MethodType, desc) => Some(format!("MethodType {}", self.utf_lit(*desc)?)),
+            _ => None,
+        }
+    }
+
+    /// Readable description of the bootstrap method behind an InvokeDynamic constant, including the
+    /// implementation method for LambdaMetafactory call sites. Intended for comments only.
+    pub(super) fn indy_target(&self, ind: u16) -> Option<String> {
+        let bsm = match self.raw_data(ind) {
+            Some(ConstData::Dyn(DynTag::InvokeDynamic, bs, _)) => self.bs.get(*bs as usize)?,
+            _ => return None,
+        };
+
+        let (tag, (cls, name, desc)) = self.method_handle_parts(bsm.bsref)?;
+        let mut s = format!("bootstrap {} {}.{}{}", tag, cls, name, desc);
+        if cls == "java/lang/invoke/LambdaMetafactory" {
+            // args are (samMethodType, implMethod, instantiatedMethodType, ...)
+            if let Some((tag, (cls, name, desc))) = bsm.args.get(1).and_then(|r| self.method_handle_parts(*r)) {
+                s += &format!(", implementation {} {}.{}{}", tag, cls, name, desc);
+            }
+        }
+        Some(s)
+    }
+
     fn ldcrhs_sub(&self, f: &mut fmt::Formatter, ind: u16, c: &ConstData) -> fmt::Result {
         use ConstData::*;
         match c {
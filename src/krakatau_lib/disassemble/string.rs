@@ -9,7 +9,8 @@ lazy_static! {
     static ref WORD_RE: Regex = Regex::new(r"\A(?:[a-zA-Z_$\(<]|\[[A-Z\[])[a-zA-Z0-9_$;/\[\(\)<>*+-]*\z").unwrap();
 }
 
-fn decode(mut iter: impl Iterator<Item = u8>, mut cb: impl FnMut(u16)) -> bool {
+// If lossy, invalid bytes are decoded as U+FFFD rather than failing
+fn decode(mut iter: impl Iterator<Item = u8>, lossy: bool, mut cb: impl FnMut(u16)) -> bool {
     while let Some(b) = iter.next() {
         match b {
             0b00000001..=0b01111111 => cb(b as u16),
@@ -30,15 +31,16 @@ fn decode(mut iter: impl Iterator<Item = u8>, mut cb: impl FnMut(u16)) -> bool {
                     }
                 }
             }
+            _ if lossy => cb(0xFFFD),
             _ => return false, // return false to indicate invalid MUTF8
         }
     }
     true
 }
 
-fn escape_sub(s: &[u8]) -> (StrLitType, String) {
+fn escape_sub(s: &[u8], lossy: bool) -> (StrLitType, String) {
     let mut out = String::with_capacity(s.len());
-    if decode(s.iter().copied(), |c| {
+    if decode(s.iter().copied(), lossy, |c| {
         match c {
             // 0..=7 => write!(&mut out, "\\{}", c),
             34 => write!(&mut out, "\\\""),
@@ -66,16 +68,29 @@ pub enum StrLitType {
 }
 
 pub(super) fn escape(s: &[u8]) -> (StrLitType, Cow<'_, str>) {
+    escape_with(s, false)
+}
+
+/// Like `escape`, but substitutes U+FFFD for invalid Modified UTF-8 rather than falling back to a byte string.
+pub(super) fn escape_lossy(s: &[u8]) -> (StrLitType, Cow<'_, str>) {
+    escape_with(s, true)
+}
+
+fn escape_with(s: &[u8], lossy: bool) -> (StrLitType, Cow<'_, str>) {
     if let Ok(s) = str::from_utf8(s) {
         if is_word(s) {
             return (StrLitType::Unquoted, Cow::from(s));
         }
     }
 
-    let (ty, s) = escape_sub(s);
+    let (ty, s) = escape_sub(s, lossy);
     (ty, Cow::from(s))
 }
 
+pub fn is_valid_mutf8(s: &[u8]) -> bool {
+    decode(s.iter().copied(), false, |_| {})
+}
+
 pub(super) fn escape_byte_string(s: &[u8]) -> String {
     let mut buf = String::with_capacity(s.len() * 4);
     for b in s {
@@ -90,7 +105,7 @@ pub fn parse_utf8(s: &[u8]) -> Option<String> {
     }
 
     let mut u16s = Vec::with_capacity(s.len());
-    if !decode(s.iter().copied(), |c16| {
+    if !decode(s.iter().copied(), false, |c16| {
         u16s.push(c16);
     }) {
         return None;
@@ -120,6 +135,15 @@ mod tests {
         assert!(!is_word("a.b"));
     }
 
+    #[test]
+    fn test_escape_lossy() {
+        assert_eq!(escape_lossy(b"a\xFFb").1.as_ref(), r#"a\uFFFDb"#);
+        assert_eq!(escape_lossy(b"\xC0\x80").1.as_ref(), r#"\u0000"#);
+        assert!(escape(b"a\xFFb") == (StrLitType::Binary, Cow::from(r#"\x61\xFF\x62"#)));
+        assert!(!is_valid_mutf8(b"a\xFFb"));
+        assert!(is_valid_mutf8(b"\xC0\x80"));
+    }
+
     #[test]
     fn test_escape() {
         assert_eq!(escape(b"hello").1.as_ref(), "hello");
@@ -1,26 +1,65 @@
 mod assemble;
-mod classfile;
+pub(crate) mod classfile;
 mod disassemble;
 mod mhtags;
 mod util;
 
 pub use assemble::assemble;
+pub use assemble::assemble_each;
+pub use assemble::encode_mutf8;
+pub use assemble::split_classes;
+pub use assemble::unescape;
 pub use assemble::AssemblerOptions;
 pub use assemble::Error as AssembleError;
 pub use classfile::ParserOptions;
 pub use disassemble::string::parse_utf8;
+pub use disassemble::instructions;
 pub use disassemble::DisassemblerOptions;
+pub use disassemble::Utf8Policy;
 
 pub fn disassemble(
     data: &[u8],
     parse_opts: ParserOptions,
     opts: DisassemblerOptions,
 ) -> Result<(Option<String>, Vec<u8>), classfile::ParseError> {
+    let mut out = Vec::new();
+    let name = disassemble_into(data, parse_opts, opts, &mut out)?;
+    Ok((name, out))
+}
+
+/// Like `disassemble`, but appends the output to a caller supplied buffer so that it can be reused across calls.
+pub fn disassemble_into(
+    data: &[u8],
+    parse_opts: ParserOptions,
+    opts: DisassemblerOptions,
+    out: &mut Vec<u8>,
+) -> Result<Option<String>, classfile::ParseError> {
     let parsed = classfile::parse(data, parse_opts)?;
+    // Only guess at the output size if the caller didn't already size the buffer
+    if out.capacity() == out.len() {
+        out.reserve(1000 + data.len() * 4);
+    }
+    disassemble_class_into(&parsed, opts, out)
+}
 
-    let name = parsed.cp.clsutf(parsed.this).and_then(parse_utf8);
+/// Like `disassemble_into`, but for an already parsed class.
+pub fn disassemble_class_into(
+    parsed: &classfile::parse::Class,
+    opts: DisassemblerOptions,
+    out: &mut Vec<u8>,
+) -> Result<Option<String>, classfile::ParseError> {
+    if opts.utf8_policy == Utf8Policy::Strict {
+        let valid = parsed.cp.0.iter().all(|c| match c {
+            classfile::cpool::Const::Utf8(s) => disassemble::string::is_valid_mutf8(s.0),
+            _ => true,
+        });
+        if !valid {
+            return Err(classfile::ParseError("Invalid Modified UTF-8 in constant pool"));
+        }
+    }
 
-    let mut out = Vec::with_capacity(1000 + data.len() * 4);
-    disassemble::disassemble(&mut out, &parsed, opts).expect("Internal error - please report this!");
-    Ok((name, out))
+    let name = parsed.cp.clsutf(parsed.this).and_then(parse_utf8);
+    disassemble::disassemble(&mut *out, parsed, opts)
+        .map_err(|_| classfile::ParseError("Internal error writing disassembly - please report this!"))?;
+    Ok(name)
 }
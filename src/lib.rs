@@ -1,17 +1,96 @@
 use std::alloc::{alloc, dealloc, Layout};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
 
 // Simple WASM interface using the original Krakatau library
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 // Include the original library with the expected path
+mod abi;
+mod analysis;
+mod ast;
+mod diff;
+mod edit;
+mod estimate;
+mod explain;
+mod jar;
 mod krakatau_lib;
+mod metadata;
+mod pool;
+mod profile;
+mod raw_class;
+mod relocate;
+mod session;
+mod synth;
+mod versions;
 
 // Re-export the original library functionality
 pub use krakatau_lib::*;
+use raw_class::RawClass;
 
+// Either both set, or null and zero when there's no response
 static mut RESPONSE_PTR: *mut u8 = std::ptr::null_mut();
 static mut RESPONSE_LEN: usize = 0;
 
+// Larger responses are almost certainly runaway output. Hosts which can't map this much memory at
+// once can read responses through get_response_chunk instead.
+const MAX_RESPONSE_LEN: usize = 64 * 1024 * 1024;
+
+// Hints above this are ignored rather than reserving memory the response cap could never use.
+const MAX_OUTPUT_HINT: usize = 16 * 1024 * 1024;
+
+// For requests which don't take parser options, such as those on assembler output.
+const PARSE_OPTS: krakatau_lib::ParserOptions = krakatau_lib::ParserOptions { no_short_code_attr: false };
+
+thread_local! {
+    // Disassembly output buffer, kept between calls so bulk workloads don't regrow it every time
+    static SCRATCH: RefCell<Vec<u8>> = RefCell::new(Vec::new());
+    // Window of the stored response copied out by get_response_chunk
+    static CHUNK: RefCell<Vec<u8>> = RefCell::new(Vec::new());
+
+    static SESSIONS: RefCell<HashMap<i32, session::AssembleSession>> = RefCell::new(HashMap::new());
+    static NEXT_SESSION_ID: Cell<i32> = Cell::new(1);
+}
+
+// Host function receiving the records written by assemble_streaming. Returns 0 on success.
+#[cfg(target_arch = "wasm32")]
+#[link(wasm_import_module = "env")]
+extern "C" {
+    fn write_chunk(ptr: *const u8, len: usize) -> i32;
+}
+
+#[cfg(target_arch = "wasm32")]
+fn host_write_chunk(data: &[u8]) -> bool {
+    unsafe { write_chunk(data.as_ptr(), data.len()) == 0 }
+}
+
+/// Receives the records written by `assemble_streaming` in native builds, returning false if the write failed.
+#[cfg(not(target_arch = "wasm32"))]
+pub type ChunkWriter = Box<dyn FnMut(&[u8]) -> bool>;
+
+#[cfg(not(target_arch = "wasm32"))]
+thread_local! {
+    static CHUNK_WRITER: RefCell<Option<ChunkWriter>> = RefCell::new(None);
+}
+
+/// Native builds have no host to import `write_chunk` from, so the writer is installed here instead.
+/// Without one, every write fails.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_chunk_writer(writer: Option<ChunkWriter>) {
+    CHUNK_WRITER.with(|w| *w.borrow_mut() = writer);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn host_write_chunk(data: &[u8]) -> bool {
+    CHUNK_WRITER.with(|w| match w.borrow_mut().as_mut() {
+        Some(writer) => writer(data),
+        None => false,
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn allocate_input_buffer(data_len: usize) -> *mut u8 {
     if data_len == 0 || data_len >= 65536 {
@@ -34,20 +113,353 @@ pub extern "C" fn free_buffer(ptr: *mut u8, len: usize) {
     }
 }
 
+/// Pre-reserves the disassembly scratch buffer to hold `bytes` of output.
+/// Hints of zero or above `MAX_OUTPUT_HINT` are ignored.
+#[no_mangle]
+pub extern "C" fn set_output_hint(bytes: usize) {
+    if bytes == 0 || bytes > MAX_OUTPUT_HINT {
+        return;
+    }
+    SCRATCH.with(|scratch| {
+        let mut buf = scratch.borrow_mut();
+        buf.clear();
+        buf.reserve(bytes);
+    });
+}
+
+/// Disassembly options shared by the single class and jar endpoints.
+#[derive(Debug, Default, Deserialize)]
+pub struct DecompileOptions {
+    #[serde(default)]
+    pub roundtrip: bool,
+    #[serde(default)]
+    pub no_short_code_attr: bool,
+    #[serde(default)]
+    pub inline_indy_targets: bool,
+    #[serde(default)]
+    pub annotate_attributes: bool,
+    #[serde(default)]
+    pub humanize_method_handles: bool,
+    /// Print an interface's methods grouped by kind. Doesn't apply to roundtrip output.
+    #[serde(default)]
+    pub group_interface_methods: bool,
+    #[serde(default)]
+    pub strip_debug: bool,
+    #[serde(default)]
+    pub utf8_policy: Utf8PolicyName,
+    #[serde(default)]
+    pub int_format: IntFormat,
+    /// Marker for the comments added by inline_indy_targets, annotate_attributes and
+    /// humanize_method_handles. Anything but the default `;` means the output won't assemble.
+    #[serde(default)]
+    pub annotation_comment_prefix: Option<String>,
+    /// Wrap lines longer than this many columns at operand boundaries, indenting the continuation
+    /// lines. This is only for reading: the assembler has no line continuations, so wrapped output
+    /// won't reassemble.
+    #[serde(default)]
+    pub wrap_columns: Option<usize>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntFormat {
+    #[default]
+    Decimal,
+    /// Negative values are written with a minus sign, e.g. -0x1
+    Hex,
+}
+
+/// JSON names for `krakatau_lib::Utf8Policy`. Unlike the library, requests default to strict.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Utf8PolicyName {
+    #[default]
+    Strict,
+    Replace,
+    RawEscape,
+}
+
+impl From<Utf8PolicyName> for krakatau_lib::Utf8Policy {
+    fn from(name: Utf8PolicyName) -> Self {
+        match name {
+            Utf8PolicyName::Strict => Self::Strict,
+            Utf8PolicyName::Replace => Self::Replace,
+            Utf8PolicyName::RawEscape => Self::RawEscape,
+        }
+    }
+}
+
+impl DecompileOptions {
+    fn parser_options(&self) -> krakatau_lib::ParserOptions {
+        krakatau_lib::ParserOptions {
+            no_short_code_attr: self.no_short_code_attr,
+        }
+    }
+
+    fn disassembler_options(&self) -> krakatau_lib::DisassemblerOptions {
+        krakatau_lib::DisassemblerOptions {
+            roundtrip: self.roundtrip,
+            inline_indy_targets: self.inline_indy_targets,
+            annotate_attributes: self.annotate_attributes,
+            humanize_method_handles: self.humanize_method_handles,
+            group_interface_methods: self.group_interface_methods,
+            strip_debug: self.strip_debug,
+            utf8_policy: self.utf8_policy.into(),
+            hex_integers: self.int_format == IntFormat::Hex,
+            annotation_comment_prefix: self.annotation_comment_prefix.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Why output produced with these options won't reassemble to an identical class with the given
+    /// access flags, if it won't.
+    fn lossy_reasons(&self, class_access: u16) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if !self.roundtrip {
+            reasons.push("constant pool order is not preserved without roundtrip".to_string());
+        }
+        if self.strip_debug {
+            reasons.push("debug attributes are stripped".to_string());
+        }
+        if self.utf8_policy == Utf8PolicyName::Replace {
+            reasons.push("invalid Modified UTF-8 is replaced".to_string());
+        }
+        if self.annotation_comment_prefix.as_deref().is_some_and(|p| p != ";") {
+            reasons.push("annotation comments don't use the assembler's comment syntax".to_string());
+        }
+        if self.wrap_columns.is_some() {
+            reasons.push("long lines are wrapped".to_string());
+        }
+        if self.disassembler_options().groups_methods(class_access) {
+            reasons.push("interface methods are reordered".to_string());
+        }
+        reasons
+    }
+
+    /// Applies the options which post-process the disassembler's text.
+    fn finish_output(&self, output: String) -> String {
+        match self.wrap_columns {
+            Some(columns) => wrap_lines(&output, columns),
+            None => output,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct DecompileRequest {
     pub file_path: String,
+    #[serde(default)]
     pub base64_content: String,
+    /// The class bytes as a JSON array, for hosts which would rather not base64 encode them. Takes
+    /// precedence over `base64_content`.
     #[serde(default)]
-    pub roundtrip: bool,
+    pub content_bytes: Option<Vec<u8>>,
     #[serde(default)]
-    pub no_short_code_attr: bool,
+    pub content_encoding: ContentEncoding,
+    /// Also report constant pool entries which aren't referenced anywhere. They're kept in roundtrip
+    /// output, and never appear in normal output.
+    #[serde(default)]
+    pub report_unused_constants: bool,
+    /// Also report methods which lack the stack map frames their class version requires
+    #[serde(default)]
+    pub verify_frames: bool,
+    /// Also return the disassembly as structured JSON in `output_ast`, from the same parse as the text
+    #[serde(default)]
+    pub dual_output: bool,
+    /// Reported as `class_name` when the class's this_class doesn't resolve to a name
+    #[serde(default)]
+    pub default_name: Option<String>,
+    /// Warn about any method using one of these opcodes, such as jsr and ret, which only appear in
+    /// classes older than version 50. Wide instructions match both "wide" and the opcode they modify.
+    #[serde(default)]
+    pub forbidden_opcodes: Vec<String>,
+    /// Fail if there are any warnings, reporting them as the error
+    #[serde(default)]
+    pub warnings_as_errors: bool,
+    #[serde(flatten)]
+    pub options: DecompileOptions,
+}
+
+/// How the decoded `base64_content` of a request is compressed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentEncoding {
+    #[default]
+    Identity,
+    /// Requires the `gzip` feature
+    Gzip,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JarDecompileRequest {
+    #[serde(default = "unknown_path")]
+    pub file_path: String,
+    pub base64_content: String,
+    #[serde(flatten)]
+    pub options: DecompileOptions,
+    /// Disassemble byte-identical classes only once, reporting the rest as aliases
+    #[serde(default)]
+    pub dedupe_identical: bool,
+    /// Stop after this many classes
+    #[serde(default)]
+    pub max_classes: Option<usize>,
+    /// Fail if the classes add up to more than this many bytes once decompressed
+    #[serde(default)]
+    pub max_total_uncompressed: Option<u64>,
+    /// Report how much work each class took, to help find expensive ones
+    #[serde(default)]
+    pub telemetry: bool,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct AssembleRequest {
     pub file_path: String,
     pub source_code: String,
+    /// Pad each class to a multiple of this many bytes, which must be a power of two no larger than
+    /// `MAX_OUTPUT_ALIGNMENT`. The padding is a zero filled `Padding` class attribute, which JVMs
+    /// ignore like any other unknown attribute, along with the Utf8 constant naming it.
+    #[serde(default)]
+    pub output_alignment: Option<usize>,
+    /// On failure, attach a longer explanation of recognized kinds of error
+    #[serde(default)]
+    pub explain: bool,
+    /// Fail if any method has more bytes of code than this. The JVM limit is 65535.
+    #[serde(default)]
+    pub max_method_code: Option<u32>,
+    /// Fail if any class declares more than this many methods, or more than this many fields
+    #[serde(default)]
+    pub max_members: Option<u16>,
+    /// Fail if assembly produces any warnings, reporting them as the error
+    #[serde(default)]
+    pub warnings_as_errors: bool,
+    /// Options for individual methods of the source
+    #[serde(default)]
+    pub methods: Vec<MethodAssembleOptions>,
+    /// Fail if a class uses features unavailable on this runtime: "jvmN" for Java N (e.g. "jvm8"),
+    /// or "android"
+    #[serde(default)]
+    pub target_profile: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssembleJarRequest {
+    #[serde(default = "unknown_path")]
+    pub file_path: String,
+    pub source_code: String,
+    /// Main attributes for META-INF/MANIFEST.MF, such as Main-Class. Manifest-Version defaults to 1.0.
+    #[serde(default)]
+    pub manifest: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MethodAssembleOptions {
+    pub method_name: String,
+    /// If omitted, applies to every method with the given name
+    #[serde(default)]
+    pub method_descriptor: Option<String>,
+    /// Source lines for code labels of the method, added as a LineNumberTable
+    #[serde(default)]
+    pub line_number_map: Option<Vec<LineNumberEntry>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LineNumberEntry {
+    pub label: String,
+    pub line: u16,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssembleFragmentsRequest {
+    #[serde(default = "unknown_path")]
+    pub file_path: String,
+    /// Concatenated in order and assembled as a single source
+    pub fragments: Vec<SourceFragment>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SplitAssemblyRequest {
+    #[serde(default = "unknown_path")]
+    pub file_path: String,
+    pub source_code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SourceFragment {
+    pub name: String,
+    pub source_fragment: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SessionUpdateRequest {
+    pub session_id: i32,
+    pub class_name: String,
+    pub source: String,
+}
+
+/// Request for endpoints which look at a single method of a class.
+#[derive(Debug, Deserialize)]
+pub struct MethodRequest {
+    #[serde(default = "unknown_path")]
+    pub file_path: String,
+    pub base64_content: String,
+    pub method_name: String,
+    pub method_descriptor: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallGraphRequest {
+    #[serde(default = "unknown_path")]
+    pub file_path: String,
+    pub base64_content: String,
+    /// Also report calls to methods of other classes
+    #[serde(default)]
+    pub include_external: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CodeSizeRequest {
+    #[serde(default = "unknown_path")]
+    pub file_path: String,
+    pub base64_content: String,
+    /// Methods with more bytes of code than this are reported in `over_threshold`
+    #[serde(default = "default_code_size_threshold")]
+    pub warning_threshold: u32,
+}
+
+fn default_code_size_threshold() -> u32 {
+    60000
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiUsageRequest {
+    #[serde(default = "unknown_path")]
+    pub file_path: String,
+    pub base64_content: String,
+    /// Only methods of classes whose internal names start with one of these are reported
+    #[serde(default = "default_api_prefixes")]
+    pub prefixes: Vec<String>,
+}
+
+fn default_api_prefixes() -> Vec<String> {
+    vec!["java/".to_string(), "javax/".to_string()]
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetSourceFileRequest {
+    #[serde(default = "unknown_path")]
+    pub file_path: String,
+    pub base64_content: String,
+    /// An empty string removes the attribute
+    pub source_file: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MinifyRequest {
+    #[serde(default = "unknown_path")]
+    pub file_path: String,
+    pub base64_content: String,
+    #[serde(flatten)]
+    pub options: edit::MinifyOptions,
 }
 
 #[derive(Debug, Serialize)]
@@ -56,6 +468,28 @@ pub struct DecompileResponse {
     pub file_path: String,
     pub output: Option<String>,
     pub error: Option<String>,
+    /// Name of the disassembled class, or the requested `default_name` if it couldn't be determined
+    pub class_name: Option<String>,
+    /// Whether this_class failed to resolve, so that `class_name` is the fallback, if any
+    pub name_resolution_failed: bool,
+    /// Whether the options used mean the output may not reassemble to an identical class
+    pub roundtrip_lossy: bool,
+    pub reasons: Vec<String>,
+    /// Only set if requested
+    pub unused_constants: Option<UnusedConstants>,
+    /// "name(desc)" of each method missing required frames, only set if requested
+    pub missing_frames: Option<Vec<String>>,
+    /// Only set if `dual_output` was requested
+    pub output_ast: Option<ast::ClassAst>,
+    /// Inconsistencies which the JVM would reject, and which often mean the class is corrupt or
+    /// obfuscated, along with any uses of `forbidden_opcodes`
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnusedConstants {
+    pub count: usize,
+    pub indices: Vec<u16>,
 }
 
 #[derive(Debug, Serialize)]
@@ -64,97 +498,434 @@ pub struct AssembleResponse {
     pub file_path: String,
     pub class_files: Option<Vec<ClassFileResult>>,
     pub error: Option<String>,
+    pub explanation: Option<explain::Explanation>,
+    /// Problems which don't stop the class from assembling, but will likely stop it from loading.
+    /// Omitted if there are none.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct ClassFileResult {
     pub name: Option<String>,
     pub base64_content: String,
+    /// Number of bytes added to reach the requested output alignment
+    pub padding: usize,
 }
 
-impl DecompileResponse {
-    pub fn success(file_path: String, output: String) -> Self {
-        Self {
-            success: true,
-            file_path,
-            output: Some(output),
-            error: None,
-        }
-    }
+/// Request for endpoints which only need a single class.
+#[derive(Debug, Deserialize)]
+pub struct ClassRequest {
+    #[serde(default = "unknown_path")]
+    pub file_path: String,
+    pub base64_content: String,
+}
 
-    pub fn error(file_path: String, error: String) -> Self {
-        Self {
-            success: false,
-            file_path,
-            output: None,
-            error: Some(error),
-        }
-    }
+fn unknown_path() -> String {
+    "unknown".to_string()
 }
 
-impl AssembleResponse {
-    pub fn success(file_path: String, class_files: Vec<ClassFileResult>) -> Self {
-        Self {
-            success: true,
-            file_path,
-            class_files: Some(class_files),
-            error: None,
-        }
-    }
+#[derive(Deserialize)]
+struct PathOnly {
+    file_path: String,
+}
 
-    pub fn error(file_path: String, error: String) -> Self {
-        Self {
-            success: false,
-            file_path,
-            class_files: None,
-            error: Some(error),
-        }
+/// Best effort recovery of the file_path of a request which failed to deserialize, so that the error
+/// response can still be matched up with it. Falls back to scanning for the first "file_path" key, in
+/// case the JSON isn't even well formed.
+fn request_path(json_data: &[u8]) -> String {
+    if let Ok(PathOnly { file_path }) = serde_json::from_slice(json_data) {
+        return file_path;
     }
+    let key = b"\"file_path\"";
+    json_data
+        .windows(key.len())
+        .position(|w| w == key)
+        .and_then(|i| {
+            let rest = &json_data[i + key.len()..];
+            let colon = rest.iter().position(|b| !b.is_ascii_whitespace()).filter(|&j| rest[j] == b':')?;
+            serde_json::Deserializer::from_slice(&rest[colon + 1..]).into_iter::<String>().next()?.ok()
+        })
+        .unwrap_or_else(unknown_path)
 }
 
-fn encode_base64(data: &[u8]) -> String {
-    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
-    let mut result = String::new();
-    let mut i = 0;
+/// Response for requests which failed before any endpoint specific work could be done.
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub success: bool,
+    pub file_path: String,
+    pub error: String,
+}
 
-    while i < data.len() {
-        let b1 = data[i];
-        let b2 = if i + 1 < data.len() { data[i + 1] } else { 0 };
-        let b3 = if i + 2 < data.len() { data[i + 2] } else { 0 };
+#[derive(Debug, Serialize)]
+pub struct JarDecompileResponse {
+    pub success: bool,
+    pub file_path: String,
+    pub classes: Vec<JarClassResult>,
+    /// Entry names of byte-identical classes, starting with the one that was disassembled
+    pub alias_groups: Vec<Vec<String>>,
+    /// Set if limits stopped the archive from being fully processed
+    pub truncated: Option<String>,
+}
 
-        let bitmap = ((b1 as u32) << 16) | ((b2 as u32) << 8) | (b3 as u32);
+#[derive(Debug, Serialize)]
+pub struct JarClassResult {
+    pub name: String,
+    pub output: Option<String>,
+    pub error: Option<String>,
+    /// Only set if requested, and the class could be disassembled
+    pub telemetry: Option<ClassTelemetry>,
+    /// Whether the options used mean the output may not reassemble to an identical class
+    pub roundtrip_lossy: bool,
+    pub reasons: Vec<String>,
+}
 
-        result.push(CHARS[((bitmap >> 18) & 63) as usize] as char);
-        result.push(CHARS[((bitmap >> 12) & 63) as usize] as char);
-        result.push(if i + 1 < data.len() { CHARS[((bitmap >> 6) & 63) as usize] as char } else { '=' });
-        result.push(if i + 2 < data.len() { CHARS[(bitmap & 63) as usize] as char } else { '=' });
+/// Measures of the work done on a class. Wall clock time isn't available to the module, so these
+/// stand in for it.
+#[derive(Debug, Serialize)]
+pub struct ClassTelemetry {
+    /// Bytecode instructions across all methods
+    pub instructions: usize,
+    /// Size of the disassembly, before any post-processing such as wrapping
+    pub output_bytes: usize,
+}
 
-        i += 3;
-    }
+#[derive(Debug, Serialize)]
+pub struct ConstantPoolResponse {
+    pub success: bool,
+    pub file_path: String,
+    /// The constant_pool_count field, one more than the number of entries
+    pub count: usize,
+    pub constants: Vec<pool::PoolEntry>,
+}
 
-    result
+#[derive(Debug, Serialize)]
+pub struct LayoutResponse {
+    pub success: bool,
+    pub file_path: String,
+    pub sections: Vec<raw_class::Section>,
 }
 
-#[no_mangle]
-pub extern "C" fn decompile_json(
-    json_ptr: *const u8,
-    json_len: usize,
-) -> i32 {
-    if json_ptr.is_null() || json_len == 0 {
-        return -1; // Error: null or empty input
-    }
+#[derive(Debug, Deserialize)]
+pub struct MetadataRequest {
+    #[serde(default = "unknown_path")]
+    pub file_path: String,
+    pub base64_content: String,
+    #[serde(flatten)]
+    pub options: metadata::MetadataOptions,
+}
 
-    // Read JSON input directly from provided pointer
-    let json_data = unsafe {
-        std::slice::from_raw_parts(json_ptr, json_len)
-    };
+#[derive(Debug, Serialize)]
+pub struct MetadataResponse {
+    pub success: bool,
+    pub file_path: String,
+    #[serde(flatten)]
+    pub metadata: metadata::ClassMetadata,
+}
 
-    // Parse JSON
-    let request: DecompileRequest = match serde_json::from_slice(json_data) {
-        Ok(req) => req,
-        Err(e) => {
-            let error_response = DecompileResponse::error(
-                "unknown".to_string(),
+#[derive(Debug, Serialize)]
+pub struct SplitAssemblyResponse {
+    pub success: bool,
+    pub file_path: String,
+    pub classes: Vec<ClassSource>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClassSource {
+    /// None if the name isn't a plain identifier, e.g. a constant pool reference
+    pub name: Option<String>,
+    pub source: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionUpdateResponse {
+    pub success: bool,
+    pub session_id: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionBuildResponse {
+    pub success: bool,
+    pub session_id: i32,
+    pub class_files: Vec<ClassFileResult>,
+    /// Sources which changed since the previous build and so were reassembled
+    pub rebuilt: Vec<String>,
+    pub assemble_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InstructionsResponse {
+    pub success: bool,
+    pub file_path: String,
+    pub instructions: Vec<analysis::Instruction>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JumpTargetsResponse {
+    pub success: bool,
+    pub file_path: String,
+    pub targets: Vec<u32>,
+    pub edges: Vec<analysis::JumpEdge>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CallGraphResponse {
+    pub success: bool,
+    pub file_path: String,
+    pub edges: Vec<analysis::CallEdge>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CodeSizeResponse {
+    pub success: bool,
+    pub file_path: String,
+    /// None if no method has code
+    pub largest: Option<analysis::MethodCodeSize>,
+    /// Largest first
+    pub over_threshold: Vec<analysis::MethodCodeSize>,
+    /// Hard limit on the code length of a method imposed by the JVM
+    pub max_code_length: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StructuralHashResponse {
+    pub success: bool,
+    pub file_path: String,
+    /// Lowercase hex SHA-256
+    pub hash: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiUsageResponse {
+    pub success: bool,
+    pub file_path: String,
+    /// Sorted "owner#name descriptor" strings
+    pub methods: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MinifyResponse {
+    pub success: bool,
+    pub file_path: String,
+    pub base64_content: String,
+    pub original_size: usize,
+    pub minified_size: usize,
+    /// Negative if minifying somehow made the class larger
+    pub bytes_saved: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AssembleSizeEstimateResponse {
+    pub success: bool,
+    pub file_path: String,
+    #[serde(flatten)]
+    pub estimate: estimate::SizeEstimate,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoundtripDiffResponse {
+    pub success: bool,
+    pub file_path: String,
+    #[serde(flatten)]
+    pub diff: diff::RoundtripDiff,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VersionInfoResponse {
+    pub success: bool,
+    pub max_supported_major: u16,
+    pub versions: Vec<versions::VersionInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SupportedAttributesResponse {
+    pub success: bool,
+    /// Decoded, and printed as dedicated directives
+    pub fully_supported: Vec<&'static str>,
+    /// Known attributes which are only preserved as raw bytes, as is any attribute not listed at all
+    pub opaque: Vec<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReduceFailureResponse {
+    pub success: bool,
+    pub file_path: String,
+    /// The disassembly error reproduced by the reduced class
+    pub failure: String,
+    pub reduced_base64: String,
+    pub original_size: usize,
+    pub reduced_size: usize,
+    /// Whether reduction stopped at the attempt limit, so the class may be reducible further
+    pub truncated: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PatchMethodRequest {
+    #[serde(default = "unknown_path")]
+    pub file_path: String,
+    pub base64_content: String,
+    /// Krakatau assembly for a single `.method` ... `.end method` block
+    pub method_source: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RelocatePackageRequest {
+    #[serde(default = "unknown_path")]
+    pub file_path: String,
+    pub base64_content: String,
+    /// Package to move, in internal form, e.g. "org/old"
+    pub from_prefix: String,
+    pub to_prefix: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssembleSizeEstimateRequest {
+    #[serde(default = "unknown_path")]
+    pub file_path: String,
+    pub source_code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RoundtripDiffRequest {
+    #[serde(default = "unknown_path")]
+    pub file_path: String,
+    pub base64_content: String,
+    /// Report at most this many differing ranges. Defaults to 100.
+    #[serde(default)]
+    pub max_ranges: Option<usize>,
+    #[serde(flatten)]
+    pub options: DecompileOptions,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SynthesizeOpcodeRequest {
+    #[serde(default = "unknown_path")]
+    pub file_path: String,
+    /// Mnemonic, as written in assembly
+    pub opcode: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AssembleJarResponse {
+    pub success: bool,
+    pub file_path: String,
+    pub base64_content: String,
+    /// Names of the entries in the jar, in order, starting with the manifest
+    pub entries: Vec<String>,
+}
+
+/// Response for endpoints which produce a modified copy of the input class.
+#[derive(Debug, Serialize)]
+pub struct ClassBytesResponse {
+    pub success: bool,
+    pub file_path: String,
+    pub base64_content: String,
+}
+
+impl DecompileResponse {
+    pub fn success(file_path: String, output: String) -> Self {
+        Self {
+            success: true,
+            file_path,
+            output: Some(output),
+            error: None,
+            class_name: None,
+            name_resolution_failed: false,
+            roundtrip_lossy: false,
+            reasons: Vec::new(),
+            unused_constants: None,
+            missing_frames: None,
+            output_ast: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn error(file_path: String, error: String) -> Self {
+        Self {
+            success: false,
+            file_path,
+            output: None,
+            error: Some(error),
+            class_name: None,
+            name_resolution_failed: false,
+            roundtrip_lossy: false,
+            reasons: Vec::new(),
+            unused_constants: None,
+            missing_frames: None,
+            output_ast: None,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl AssembleResponse {
+    pub fn success(file_path: String, class_files: Vec<ClassFileResult>) -> Self {
+        Self {
+            success: true,
+            file_path,
+            class_files: Some(class_files),
+            error: None,
+            explanation: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn error(file_path: String, error: String) -> Self {
+        Self {
+            success: false,
+            file_path,
+            class_files: None,
+            error: Some(error),
+            explanation: None,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+fn encode_base64(data: &[u8]) -> String {
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        let b1 = data[i];
+        let b2 = if i + 1 < data.len() { data[i + 1] } else { 0 };
+        let b3 = if i + 2 < data.len() { data[i + 2] } else { 0 };
+
+        let bitmap = ((b1 as u32) << 16) | ((b2 as u32) << 8) | (b3 as u32);
+
+        result.push(CHARS[((bitmap >> 18) & 63) as usize] as char);
+        result.push(CHARS[((bitmap >> 12) & 63) as usize] as char);
+        result.push(if i + 1 < data.len() { CHARS[((bitmap >> 6) & 63) as usize] as char } else { '=' });
+        result.push(if i + 2 < data.len() { CHARS[(bitmap & 63) as usize] as char } else { '=' });
+
+        i += 3;
+    }
+
+    result
+}
+
+#[no_mangle]
+pub extern "C" fn decompile_json(
+    json_ptr: *const u8,
+    json_len: usize,
+) -> i32 {
+    if json_ptr.is_null() || json_len == 0 {
+        free_response();
+        return -1; // Error: null or empty input
+    }
+
+    // Read JSON input directly from provided pointer
+    let json_data = unsafe {
+        std::slice::from_raw_parts(json_ptr, json_len)
+    };
+
+    // Parse JSON
+    let mut request: DecompileRequest = match serde_json::from_slice(json_data) {
+        Ok(req) => req,
+        Err(e) => {
+            let error_response = DecompileResponse::error(
+                request_path(json_data),
                 format!("JSON parse error: {}", e),
             );
             return store_response(error_response);
@@ -162,57 +933,167 @@ pub extern "C" fn decompile_json(
     };
 
     // Decode base64 content
-    let class_data = match decode_base64(&request.base64_content) {
-        Ok(data) => data,
-        Err(e) => {
+    let class_data = match request.content_bytes.take() {
+        Some(bytes) if bytes.is_empty() => {
             let error_response = DecompileResponse::error(
                 request.file_path.clone(),
-                format!("Base64 decode error: {}", e),
+                "content_bytes must not be empty".to_string(),
             );
             return store_response(error_response);
         }
+        Some(bytes) => bytes,
+        None => match decode_base64(&request.base64_content) {
+            Ok(data) => data,
+            Err(e) => {
+                let error_response = DecompileResponse::error(
+                    request.file_path.clone(),
+                    format!("Base64 decode error: {}", e),
+                );
+                return store_response(error_response);
+            }
+        },
+    };
+    let class_data = match decode_content(class_data, request.content_encoding) {
+        Ok(data) => data,
+        Err(e) => return store_response(DecompileResponse::error(request.file_path.clone(), e)),
     };
 
     // Set up decompilation options using original library types
-    let opts = krakatau_lib::DisassemblerOptions {
-        roundtrip: request.roundtrip,
+    let opts = request.options.disassembler_options();
+    let parse_opts = request.options.parser_options();
+
+    // Parse once, and share the result between the text output and anything else requested
+    let class = match krakatau_lib::classfile::parse(&class_data, parse_opts) {
+        Ok(class) => class,
+        Err(e) => {
+            return store_response(DecompileResponse::error(
+                request.file_path.clone(),
+                format!("Decompilation error: {:?}", e),
+            ))
+        }
     };
-    let parse_opts = krakatau_lib::ParserOptions {
-        no_short_code_attr: request.no_short_code_attr,
+    let unused_constants = request.report_unused_constants.then(|| {
+        let indices = edit::unused_constants(&class);
+        UnusedConstants {
+            count: indices.len(),
+            indices,
+        }
+    });
+    let mut warnings = consistency_warnings(&class);
+    match forbidden_opcode_warnings(&class, &request.forbidden_opcodes) {
+        Ok(forbidden) => warnings.extend(forbidden),
+        Err(e) => return store_response(DecompileResponse::error(request.file_path.clone(), e)),
+    }
+    if request.warnings_as_errors && !warnings.is_empty() {
+        return store_response(DecompileResponse::error(request.file_path.clone(), warnings.join("\n")));
+    }
+    let missing_frames = request.verify_frames.then(|| analysis::missing_frames(&class));
+    let output_ast = if request.dual_output {
+        match ast::class_ast(&class, &opts) {
+            Ok(tree) => Some(tree),
+            Err(e) => return store_response(DecompileResponse::error(request.file_path.clone(), e)),
+        }
+    } else {
+        None
     };
 
     // Perform real decompilation using original library
-    let response = match krakatau_lib::disassemble(&class_data, parse_opts, opts) {
-        Ok((_name, out)) => {
-            // Convert the output bytes to UTF-8 string
-            match String::from_utf8(out) {
-                Ok(output) => {
-                    DecompileResponse::success(request.file_path.clone(), output)
-                }
-                Err(e) => {
-                    DecompileResponse::error(
-                        request.file_path.clone(),
-                        format!("Output encoding error: {}", e),
-                    )
+    let response = SCRATCH.with(|scratch| {
+        let mut out = scratch.borrow_mut();
+        out.clear();
+        // Only guess at the output size if there's no hint already sizing the buffer
+        if out.capacity() == 0 {
+            out.reserve(1000 + class_data.len() * 4);
+        }
+        match krakatau_lib::disassemble_class_into(&class, opts, &mut out) {
+            Ok(name) => {
+                // Convert the output bytes to UTF-8 string
+                match std::str::from_utf8(&out) {
+                    Ok(output) => {
+                        let output = request.options.finish_output(output.to_owned());
+                        let mut response = DecompileResponse::success(request.file_path.clone(), output);
+                        response.reasons = request.options.lossy_reasons(class.access);
+                        response.roundtrip_lossy = !response.reasons.is_empty();
+                        response.unused_constants = unused_constants;
+                        response.missing_frames = missing_frames;
+                        response.output_ast = output_ast;
+                        response.name_resolution_failed = name.is_none();
+                        response.class_name = name.or_else(|| request.default_name.clone());
+                        response.warnings = warnings;
+                        response
+                    }
+                    Err(e) => {
+                        DecompileResponse::error(
+                            request.file_path.clone(),
+                            format!("Output encoding error: {}", e),
+                        )
+                    }
                 }
             }
+            Err(err) => {
+                DecompileResponse::error(
+                    request.file_path.clone(),
+                    format!("Decompilation error: {:?}", err),
+                )
+            }
         }
-        Err(err) => {
-            DecompileResponse::error(
-                request.file_path.clone(),
-                format!("Decompilation error: {:?}", err),
-            )
-        }
-    };
+    });
     store_response(response)
 }
 
+/// Reported instead of assembling nothing, which is almost certainly a mistake by the caller
+const EMPTY_SOURCE_ERROR: &str = "empty source: no class declarations found";
+
+/// Why source assembled by one of the assemble endpoints produced no classes
+enum AssembleFailure {
+    /// The source is blank, or consists only of comments
+    EmptySource,
+    Assembly(krakatau_lib::AssembleError),
+}
+
+/// Assembles source for the assemble endpoints, passing each class to `emit` like `assemble_each`.
+/// Source which declares no classes at all is an error.
+fn assemble_source_each(
+    source: &str,
+    opts: krakatau_lib::AssemblerOptions,
+    mut emit: impl FnMut(Option<String>, Vec<u8>) -> bool,
+) -> Result<(), AssembleFailure> {
+    if source.trim().is_empty() {
+        return Err(AssembleFailure::EmptySource);
+    }
+    let mut emitted = false;
+    krakatau_lib::assemble_each(source, opts, |name, data| {
+        emitted = true;
+        emit(name, data)
+    })
+    .map_err(AssembleFailure::Assembly)?;
+    if emitted {
+        Ok(())
+    } else {
+        Err(AssembleFailure::EmptySource)
+    }
+}
+
+/// Like `assemble_source_each`, but collects the classes.
+fn assemble_source(
+    source: &str,
+    opts: krakatau_lib::AssemblerOptions,
+) -> Result<Vec<(Option<String>, Vec<u8>)>, AssembleFailure> {
+    let mut classes = Vec::new();
+    assemble_source_each(source, opts, |name, data| {
+        classes.push((name, data));
+        true
+    })?;
+    Ok(classes)
+}
+
 #[no_mangle]
 pub extern "C" fn assemble_json(
     json_ptr: *const u8,
     json_len: usize,
 ) -> i32 {
     if json_ptr.is_null() || json_len == 0 {
+        free_response();
         return -1; // Error: null or empty input
     }
 
@@ -226,163 +1107,3607 @@ pub extern "C" fn assemble_json(
         Ok(req) => req,
         Err(e) => {
             let error_response = AssembleResponse::error(
-                "unknown".to_string(),
+                request_path(json_data),
                 format!("JSON parse error: {}", e),
             );
             return store_assemble_response(error_response);
         }
     };
 
+    let alignment = request.output_alignment.unwrap_or(1);
+    if !alignment.is_power_of_two() || alignment > MAX_OUTPUT_ALIGNMENT {
+        let error_response = AssembleResponse::error(
+            request.file_path.clone(),
+            format!("Output alignment must be a power of two up to {}, got {}", MAX_OUTPUT_ALIGNMENT, alignment),
+        );
+        return store_assemble_response(error_response);
+    }
+
+    let profile = match request.target_profile.as_deref().map(profile::Profile::parse).transpose() {
+        Ok(profile) => profile,
+        Err(e) => return store_assemble_response(AssembleResponse::error(request.file_path.clone(), e)),
+    };
+
     // Set up assembly options using original library types
     let opts = krakatau_lib::AssemblerOptions {};
 
+    let (source, insertions) = match apply_method_options(&request.source_code, &request.methods) {
+        Ok(rewritten) => rewritten,
+        Err(e) => return store_assemble_response(AssembleResponse::error(request.file_path.clone(), e)),
+    };
+
     // Perform assembly using original library
-    let response = match krakatau_lib::assemble(&request.source_code, opts) {
+    let response = match assemble_source(&source, opts) {
         Ok(classes) => {
             let mut class_results = Vec::new();
+            let mut warnings = Vec::new();
 
-            for (name, data) in classes {
+            for (name, mut data) in classes {
+                let class = match krakatau_lib::classfile::parse(&data, PARSE_OPTS) {
+                    Ok(class) => class,
+                    Err(e) => {
+                        let error = format!("Class parse error: {:?}", e);
+                        return store_assemble_response(AssembleResponse::error(request.file_path.clone(), error));
+                    }
+                };
+                if let Err(e) = check_size_limits(&class, &request) {
+                    return store_assemble_response(AssembleResponse::error(request.file_path.clone(), e));
+                }
+                if let Some(errors) = profile.as_ref().map(|profile| profile.check(&class)).filter(|e| !e.is_empty()) {
+                    let error = errors.join("\n");
+                    return store_assemble_response(AssembleResponse::error(request.file_path.clone(), error));
+                }
+                warnings.extend(assembly_warnings(&class));
+                let padding = if alignment > 1 {
+                    match pad_class(&data, alignment) {
+                        Ok(padded) => {
+                            let padding = padded.len() - data.len();
+                            data = padded;
+                            padding
+                        }
+                        Err(e) => return store_assemble_response(AssembleResponse::error(request.file_path.clone(), e)),
+                    }
+                } else {
+                    0
+                };
                 let base64_content = encode_base64(&data);
                 class_results.push(ClassFileResult {
                     name,
                     base64_content,
+                    padding,
                 });
             }
 
-            AssembleResponse::success(request.file_path.clone(), class_results)
+            let mut response = if request.warnings_as_errors && !warnings.is_empty() {
+                AssembleResponse::error(request.file_path.clone(), warnings.join("\n"))
+            } else {
+                AssembleResponse::success(request.file_path.clone(), class_results)
+            };
+            response.warnings = warnings;
+            response
+        }
+        Err(AssembleFailure::EmptySource) => {
+            AssembleResponse::error(request.file_path.clone(), EMPTY_SOURCE_ERROR.to_string())
         }
-        Err(err) => {
-            AssembleResponse::error(
+        Err(AssembleFailure::Assembly(err)) => {
+            // Report locations in the source as the caller wrote it
+            let err = err.map_offsets(|pos| original_offset(&insertions, pos));
+            let mut response = AssembleResponse::error(
                 request.file_path.clone(),
                 format!("Assembly error: {:?}", err),
-            )
+            );
+            if request.explain {
+                response.explanation = explain::explain(&err);
+            }
+            response
         }
     };
 
     store_assemble_response(response)
 }
 
+/// Assembles every class in the source into a jar, with a manifest.
 #[no_mangle]
-pub extern "C" fn get_response_length() -> i32 {
-    unsafe {
-        RESPONSE_LEN as i32
+pub extern "C" fn assemble_jar_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: AssembleJarRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+
+    let manifest = match jar::manifest(&request.manifest.unwrap_or_default()) {
+        Ok(manifest) => manifest,
+        Err(e) => return store_error(request.file_path, e),
+    };
+    let classes = match assemble_source(&request.source_code, krakatau_lib::AssemblerOptions {}) {
+        Ok(classes) => classes,
+        Err(AssembleFailure::EmptySource) => return store_error(request.file_path, EMPTY_SOURCE_ERROR.to_string()),
+        Err(AssembleFailure::Assembly(err)) => return store_error(request.file_path, format!("Assembly error: {:?}", err)),
+    };
+    let mut entries = Vec::with_capacity(classes.len());
+    for (name, data) in classes {
+        match name {
+            Some(name) => entries.push((format!("{}.class", name), data)),
+            None => return store_error(request.file_path, "Class has missing or invalid name".to_string()),
+        }
+    }
+
+    match jar::write_jar(&manifest, &entries) {
+        Ok(data) => store(&AssembleJarResponse {
+            success: true,
+            file_path: request.file_path,
+            base64_content: encode_base64(&data),
+            entries: std::iter::once("META-INF/MANIFEST.MF".to_string())
+                .chain(entries.into_iter().map(|(name, _)| name))
+                .collect(),
+        }),
+        Err(e) => store_error(request.file_path, e),
     }
 }
 
+/// Estimates the total size of the classes the source would assemble to, without assembling it. This
+/// is much cheaper than assembling, but only an approximation, and doesn't check the source for errors.
 #[no_mangle]
-pub extern "C" fn get_response_ptr() -> *const u8 {
-    unsafe {
-        RESPONSE_PTR
+pub extern "C" fn assemble_size_estimate_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: AssembleSizeEstimateRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+
+    match estimate::estimate(&request.source_code) {
+        Ok(estimate) => store(&AssembleSizeEstimateResponse {
+            success: true,
+            file_path: request.file_path,
+            estimate,
+        }),
+        Err(e) => store_error(request.file_path, e),
     }
 }
 
+/// Assembles source split across named fragments, attributing errors to the fragment and line they occur in.
+#[no_mangle]
+pub extern "C" fn assemble_fragments_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: AssembleFragmentsRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
 
-fn store_response(response: DecompileResponse) -> i32 {
-    // Serialize response to JSON
-    let json_string = match serde_json::to_string(&response) {
-        Ok(json) => json,
-        Err(e) => {
-            let fallback = DecompileResponse::error(
-                "unknown".to_string(),
-                format!("JSON serialization error: {}", e),
-            );
-            serde_json::to_string(&fallback).unwrap_or_default()
+    // Start offset of each fragment within the combined source
+    let mut starts = Vec::with_capacity(request.fragments.len());
+    let mut source = String::new();
+    for frag in &request.fragments {
+        starts.push(source.len());
+        source.push_str(&frag.source_fragment);
+        if !source.ends_with('\n') {
+            source.push('\n');
         }
+    }
+
+    let response = match assemble_source(&source, AssemblerOptions {}) {
+        Ok(classes) => {
+            let class_results = classes
+                .into_iter()
+                .map(|(name, data)| ClassFileResult {
+                    name,
+                    base64_content: encode_base64(&data),
+                    padding: 0,
+                })
+                .collect();
+            AssembleResponse::success(request.file_path, class_results)
+        }
+        Err(AssembleFailure::EmptySource) => AssembleResponse::error(request.file_path, EMPTY_SOURCE_ERROR.to_string()),
+        Err(AssembleFailure::Assembly(err)) => {
+            let messages: Vec<_> = err
+                .locations()
+                .map(|(msg, offset)| {
+                    let i = starts.partition_point(|&start| start <= offset).saturating_sub(1);
+                    let line = source[starts[i]..offset].matches('\n').count() + 1;
+                    format!("{}:{}: {}", request.fragments[i].name, line, msg)
+                })
+                .collect();
+            AssembleResponse::error(request.file_path, format!("Assembly error: {}", messages.join("; ")))
+        }
+    };
+    store_assemble_response(response)
+}
+
+/// Splits source containing several classes into the source of each class, without assembling it.
+#[no_mangle]
+pub extern "C" fn split_assembly_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: SplitAssemblyRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
     };
 
-    store_json_response(json_string)
+    match krakatau_lib::split_classes(&request.source_code) {
+        Ok(blocks) => store(&SplitAssemblyResponse {
+            success: true,
+            file_path: request.file_path,
+            classes: blocks
+                .into_iter()
+                .map(|(name, source)| ClassSource {
+                    name: name.map(str::to_owned),
+                    source: source.to_owned(),
+                })
+                .collect(),
+        }),
+        Err(e) => store_error(request.file_path, format!("Assembly error: {:?}", e)),
+    }
 }
 
-fn store_assemble_response(response: AssembleResponse) -> i32 {
-    // Serialize response to JSON
-    let json_string = match serde_json::to_string(&response) {
-        Ok(json) => json,
-        Err(e) => {
-            let fallback = AssembleResponse::error(
-                "unknown".to_string(),
-                format!("JSON serialization error: {}", e),
-            );
-            serde_json::to_string(&fallback).unwrap_or_default()
-        }
+/// Starts an incremental assembly session, returning its id.
+#[no_mangle]
+pub extern "C" fn assemble_session_begin() -> i32 {
+    let id = NEXT_SESSION_ID.with(|next| next.replace(next.get() + 1));
+    SESSIONS.with(|sessions| sessions.borrow_mut().insert(id, Default::default()));
+    id
+}
+
+/// Replaces the source of one class in a session.
+#[no_mangle]
+pub extern "C" fn assemble_session_update(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: SessionUpdateRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
     };
 
-    store_json_response(json_string)
+    let found = SESSIONS.with(|sessions| match sessions.borrow_mut().get_mut(&request.session_id) {
+        Some(session) => {
+            session.update(request.class_name.clone(), request.source);
+            true
+        }
+        None => false,
+    });
+    if !found {
+        return store_error(unknown_path(), format!("Unknown session {}", request.session_id));
+    }
+    store(&SessionUpdateResponse {
+        success: true,
+        session_id: request.session_id,
+    })
 }
 
-fn store_json_response(json_string: String) -> i32 {
-    let json_bytes = json_string.into_bytes();
-    let len = json_bytes.len();
-    if len >= 524_288 { return -1; }
+/// Reassembles the classes changed since the last build and returns the output of every class in the session.
+#[no_mangle]
+pub extern "C" fn assemble_session_build(session_id: i32) -> i32 {
+    SESSIONS.with(|sessions| {
+        let mut sessions = sessions.borrow_mut();
+        let session = match sessions.get_mut(&session_id) {
+            Some(session) => session,
+            None => return store_error(unknown_path(), format!("Unknown session {}", session_id)),
+        };
 
-    unsafe {
-        // free previous
-        if !RESPONSE_PTR.is_null() && RESPONSE_LEN != 0 {
-            if let Some(layout) = Layout::array::<u8>(RESPONSE_LEN).ok() {
-                dealloc(RESPONSE_PTR, layout);
+        let result = match session.build() {
+            Ok(result) => result,
+            Err(e) => return store_error(unknown_path(), e),
+        };
+        let rebuilt = result.rebuilt;
+        let class_files = result
+            .classes
+            .into_iter()
+            .map(|(name, data)| ClassFileResult {
+                name: name.clone(),
+                base64_content: encode_base64(data),
+                padding: 0,
+            })
+            .collect();
+        let response = SessionBuildResponse {
+            success: true,
+            session_id,
+            class_files,
+            rebuilt,
+            assemble_count: session.assemble_count,
+        };
+        store(&response)
+    })
+}
+
+/// Discards a session. Returns 1 if it existed, 0 otherwise.
+#[no_mangle]
+pub extern "C" fn assemble_session_end(session_id: i32) -> i32 {
+    SESSIONS.with(|sessions| sessions.borrow_mut().remove(&session_id).is_some() as i32)
+}
+
+/// Disassembles every class in a jar or zip archive.
+#[no_mangle]
+pub extern "C" fn decompile_jar_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: JarDecompileRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+    let jar_data = match decode_base64(&request.base64_content) {
+        Ok(data) => data,
+        Err(e) => return store_error(request.file_path, format!("Base64 decode error: {}", e)),
+    };
+    let limits = jar::Limits {
+        max_classes: request.max_classes,
+        max_total_uncompressed: request.max_total_uncompressed,
+    };
+    let jar = match jar::read_classes(&jar_data, limits) {
+        Ok(jar) => jar,
+        Err(e) => return store_error(request.file_path, e),
+    };
+
+    let parse_opts = request.options.parser_options();
+    let opts = request.options.disassembler_options();
+
+    let mut classes = Vec::with_capacity(jar.classes.len());
+    let mut alias_groups: Vec<Vec<String>> = Vec::new();
+    // Maps class bytes to the index of their alias group, if deduplicating
+    let mut seen: HashMap<&[u8], usize> = HashMap::new();
+    for (name, data) in &jar.classes {
+        if request.dedupe_identical {
+            if let Some(&group) = seen.get(data.as_slice()) {
+                alias_groups[group].push(name.clone());
+                continue;
             }
+            seen.insert(data.as_slice(), alias_groups.len());
+            alias_groups.push(vec![name.clone()]);
         }
 
-        let layout = match Layout::array::<u8>(len) { Ok(l) => l, Err(_) => return -1 };
-        let ptr = alloc(layout);
-        if ptr.is_null() { return -1; }
-
-        std::ptr::copy_nonoverlapping(json_bytes.as_ptr(), ptr, len);
-        RESPONSE_PTR = ptr;
-        RESPONSE_LEN = len;
-        len as i32
+        let mut out = Vec::new();
+        let result = krakatau_lib::classfile::parse(data, parse_opts).and_then(|class| {
+            krakatau_lib::disassemble_class_into(&class, opts.clone(), &mut out)?;
+            let instructions = class.methods.iter().filter_map(analysis::method_code).map(|code| code.bytecode.0.len());
+            Ok((instructions.sum::<usize>(), class.access))
+        });
+        let mut reasons = Vec::new();
+        let (output, error, telemetry) = match result {
+            Ok((instructions, access)) => {
+                reasons = request.options.lossy_reasons(access);
+                let telemetry = request.telemetry.then_some(ClassTelemetry {
+                    instructions,
+                    output_bytes: out.len(),
+                });
+                match String::from_utf8(out) {
+                    Ok(output) => (Some(request.options.finish_output(output)), None, telemetry),
+                    Err(e) => (None, Some(format!("Output encoding error: {}", e)), telemetry),
+                }
+            }
+            Err(err) => (None, Some(format!("Decompilation error: {:?}", err)), None),
+        };
+        classes.push(JarClassResult {
+            name: name.clone(),
+            output,
+            error,
+            telemetry,
+            roundtrip_lossy: !reasons.is_empty(),
+            reasons,
+        });
     }
+    alias_groups.retain(|group| group.len() > 1);
+
+    store(&JarDecompileResponse {
+        success: true,
+        file_path: request.file_path,
+        classes,
+        alias_groups,
+        truncated: jar.truncated,
+    })
 }
 
+/// Shrinks a class which fails to disassemble by repeatedly dropping methods, fields and attributes
+/// for as long as the same error still occurs, for attaching to bug reports.
 #[no_mangle]
-pub extern "C" fn free_response() {
-    unsafe {
-        if !RESPONSE_PTR.is_null() && RESPONSE_LEN != 0 {
-            if let Some(layout) = Layout::array::<u8>(RESPONSE_LEN).ok() {
-                dealloc(RESPONSE_PTR, layout);
-            }
+pub extern "C" fn reduce_failure_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let (request, class_data) = match read_class_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+
+    let failure = match disassemble_error(&class_data) {
+        Some(e) => e,
+        None => {
+            return store_error(request.file_path, "Class disassembles successfully, nothing to reduce".to_string());
+        }
+    };
+
+    // If even the top level structure is unreadable, the input is as small as we can make it
+    let (reduced, truncated) = match RawClass::parse(&class_data) {
+        Ok(raw) => {
+            let (raw, truncated) = reduce_failure(raw, failure);
+            (raw.to_bytes(), truncated)
+        }
+        Err(_) => (class_data.clone(), false),
+    };
+
+    store(&ReduceFailureResponse {
+        success: true,
+        file_path: request.file_path,
+        failure: failure.to_string(),
+        reduced_base64: encode_base64(&reduced),
+        original_size: class_data.len(),
+        reduced_size: reduced.len(),
+        truncated,
+    })
+}
+
+fn disassemble_error(data: &[u8]) -> Option<&'static str> {
+    let opts = krakatau_lib::DisassemblerOptions::default();
+    krakatau_lib::disassemble(data, PARSE_OPTS, opts).err().map(|e| e.0)
+}
+
+/// Most candidate classes tried by reduce_failure_json, each of which is disassembled in full
+const MAX_REDUCE_ATTEMPTS: usize = 2000;
+
+/// Returns the reduced class, and whether the attempt limit was reached first.
+fn reduce_failure<'a>(mut cur: RawClass<'a>, failure: &'static str) -> (RawClass<'a>, bool) {
+    // Try dropping each part once, in order. Dropping a part shifts the later ones down, so the same
+    // position is tried again after a successful drop.
+    let mut i = 0;
+    for _ in 0..MAX_REDUCE_ATTEMPTS {
+        let Some(&part) = cur.parts().get(i) else {
+            return (cur, false);
+        };
+        let next = cur.without(part);
+        if disassemble_error(&next.to_bytes()) == Some(failure) {
+            cur = next;
+        } else {
+            i += 1;
         }
-        RESPONSE_PTR = std::ptr::null_mut();
-        RESPONSE_LEN = 0;
     }
+    let done = i >= cur.parts().len();
+    (cur, !done)
 }
 
-fn decode_base64(input: &str) -> Result<Vec<u8>, &'static str> {
-    let input = input.trim();
-    if input.is_empty() {
-        return Ok(Vec::new());
+/// Replaces a method's definition with newly assembled source, matching it by name and descriptor.
+#[no_mangle]
+pub extern "C" fn patch_method_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: PatchMethodRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+    let class_data = match decode_base64(&request.base64_content) {
+        Ok(data) => data,
+        Err(e) => return store_error(request.file_path, format!("Base64 decode error: {}", e)),
+    };
+
+    match edit::patch_method(&class_data, &request.method_source) {
+        Ok(data) => store(&ClassBytesResponse {
+            success: true,
+            file_path: request.file_path,
+            base64_content: encode_base64(&data),
+        }),
+        Err(e) => store_error(request.file_path, e),
     }
+}
 
-    // Remove padding
-    let input = input.trim_end_matches('=');
+/// Stores every constant pool entry, decoded and with references resolved, in pool order.
+#[no_mangle]
+pub extern "C" fn constant_pool_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let (request, class_data) = match read_class_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
 
-    let mut result = Vec::new();
-    let mut buffer = 0u32;
-    let mut bits = 0;
+    match krakatau_lib::classfile::parse(&class_data, PARSE_OPTS) {
+        Ok(class) => store(&ConstantPoolResponse {
+            success: true,
+            file_path: request.file_path,
+            count: class.cp.0.len(),
+            constants: pool::entries(&class.cp),
+        }),
+        Err(e) => store_error(request.file_path, format!("Class parse error: {:?}", e)),
+    }
+}
 
-    for &c in input.as_bytes() {
-        let val = if c >= b'A' && c <= b'Z' {
-            c - b'A'
-        } else if c >= b'a' && c <= b'z' {
-            c - b'a' + 26
-        } else if c >= b'0' && c <= b'9' {
-            c - b'0' + 52
-        } else if c == b'+' {
-            62
-        } else if c == b'/' {
-            63
-        } else {
-            return Err("Invalid base64 character");
-        };
+/// Reports the byte range of each top level section of a class.
+#[no_mangle]
+pub extern "C" fn layout_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let (request, class_data) = match read_class_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
 
-        buffer = (buffer << 6) | (val as u32);
-        bits += 6;
+    match raw_class::layout(&class_data) {
+        Ok(sections) => store(&LayoutResponse {
+            success: true,
+            file_path: request.file_path,
+            sections,
+        }),
+        Err(e) => store_error(request.file_path, format!("Class parse error: {}", e)),
+    }
+}
 
-        if bits >= 8 {
-            bits -= 8;
-            result.push((buffer >> bits) as u8);
-            buffer &= (1 << bits) - 1;
+/// Reports structured information about a class decoded from its attributes.
+#[no_mangle]
+pub extern "C" fn metadata_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: MetadataRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+    let class_data = match decode_base64(&request.base64_content) {
+        Ok(data) => data,
+        Err(e) => return store_error(request.file_path, format!("Base64 decode error: {}", e)),
+    };
+
+    match krakatau_lib::classfile::parse(&class_data, PARSE_OPTS) {
+        Ok(class) => store(&MetadataResponse {
+            success: true,
+            file_path: request.file_path,
+            metadata: metadata::ClassMetadata::new(&class, &request.options),
+        }),
+        Err(e) => store_error(request.file_path, format!("Class parse error: {:?}", e)),
+    }
+}
+
+/// Lists the instructions of a single method, selected by name and descriptor.
+#[no_mangle]
+pub extern "C" fn instructions_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: MethodRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+    let class_data = match decode_base64(&request.base64_content) {
+        Ok(data) => data,
+        Err(e) => return store_error(request.file_path, format!("Base64 decode error: {}", e)),
+    };
+
+    let class = match krakatau_lib::classfile::parse(&class_data, PARSE_OPTS) {
+        Ok(class) => class,
+        Err(e) => return store_error(request.file_path, format!("Class parse error: {:?}", e)),
+    };
+    let method = match analysis::find_method(&class, &request.method_name, &request.method_descriptor) {
+        Some(m) => m,
+        None => {
+            let error = format!("No method {}{} in class", request.method_name, request.method_descriptor);
+            return store_error(request.file_path, error);
+        }
+    };
+
+    let code = match analysis::method_code(method) {
+        Some(code) => code,
+        None => return store_error(request.file_path, "Method has no code".to_string()),
+    };
+    store(&InstructionsResponse {
+        success: true,
+        file_path: request.file_path,
+        instructions: analysis::instructions(&class.cp, code),
+    })
+}
+
+/// Reports the jumps and exception handlers in a method, as the edges of a control flow graph.
+#[no_mangle]
+pub extern "C" fn jump_targets_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: MethodRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+    let class_data = match decode_base64(&request.base64_content) {
+        Ok(data) => data,
+        Err(e) => return store_error(request.file_path, format!("Base64 decode error: {}", e)),
+    };
+
+    let class = match krakatau_lib::classfile::parse(&class_data, PARSE_OPTS) {
+        Ok(class) => class,
+        Err(e) => return store_error(request.file_path, format!("Class parse error: {:?}", e)),
+    };
+    let code = match analysis::find_method(&class, &request.method_name, &request.method_descriptor) {
+        Some(m) => match analysis::method_code(m) {
+            Some(code) => code,
+            None => return store_error(request.file_path, "Method has no code".to_string()),
+        },
+        None => {
+            let error = format!("No method {}{} in class", request.method_name, request.method_descriptor);
+            return store_error(request.file_path, error);
         }
+    };
+
+    let (targets, edges) = analysis::jump_edges(code);
+    store(&JumpTargetsResponse {
+        success: true,
+        file_path: request.file_path,
+        targets,
+        edges,
+    })
+}
+
+/// Reports which methods of a class call which, from the invoke instructions in each method.
+#[no_mangle]
+pub extern "C" fn call_graph_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: CallGraphRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+    let class_data = match decode_base64(&request.base64_content) {
+        Ok(data) => data,
+        Err(e) => return store_error(request.file_path, format!("Base64 decode error: {}", e)),
+    };
+
+    match krakatau_lib::classfile::parse(&class_data, PARSE_OPTS) {
+        Ok(class) => store(&CallGraphResponse {
+            success: true,
+            file_path: request.file_path,
+            edges: analysis::call_graph(&class, request.include_external),
+        }),
+        Err(e) => store_error(request.file_path, format!("Class parse error: {:?}", e)),
     }
+}
 
-    Ok(result)
-}
\ No newline at end of file
+/// Reports the largest method in a class, and any methods close to the 65535 byte code length limit.
+#[no_mangle]
+pub extern "C" fn code_size_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: CodeSizeRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+    let class_data = match decode_base64(&request.base64_content) {
+        Ok(data) => data,
+        Err(e) => return store_error(request.file_path, format!("Base64 decode error: {}", e)),
+    };
+
+    match krakatau_lib::classfile::parse(&class_data, PARSE_OPTS) {
+        Ok(class) => {
+            let sizes = analysis::code_sizes(&class);
+            let largest = sizes.first().cloned();
+            store(&CodeSizeResponse {
+                success: true,
+                file_path: request.file_path,
+                largest,
+                over_threshold: sizes.into_iter().take_while(|s| s.code_length > request.warning_threshold).collect(),
+                max_code_length: 65535,
+            })
+        }
+        Err(e) => store_error(request.file_path, format!("Class parse error: {:?}", e)),
+    }
+}
+
+/// Lists the methods of other classes (by default, JDK classes) which a class invokes.
+#[no_mangle]
+pub extern "C" fn api_usage_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: ApiUsageRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+    let class_data = match decode_base64(&request.base64_content) {
+        Ok(data) => data,
+        Err(e) => return store_error(request.file_path, format!("Base64 decode error: {}", e)),
+    };
+
+    match krakatau_lib::classfile::parse(&class_data, PARSE_OPTS) {
+        Ok(class) => store(&ApiUsageResponse {
+            success: true,
+            file_path: request.file_path,
+            methods: analysis::api_usage(&class, &request.prefixes),
+        }),
+        Err(e) => store_error(request.file_path, format!("Class parse error: {:?}", e)),
+    }
+}
+
+/// Hashes a class in a way which ignores debug information and constant pool order, so that classes
+/// compiled from the same code with different debug settings hash the same.
+#[no_mangle]
+pub extern "C" fn structural_hash_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let (request, class_data) = match read_class_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+
+    match edit::normalized_disassembly(&class_data) {
+        Ok(text) => {
+            let digest = Sha256::digest(text.as_bytes());
+            store(&StructuralHashResponse {
+                success: true,
+                file_path: request.file_path,
+                hash: digest.iter().map(|b| format!("{:02x}", b)).collect(),
+            })
+        }
+        Err(e) => store_error(request.file_path, e),
+    }
+}
+
+/// Moves the classes of one package to another throughout a class, as when shading a dependency.
+#[no_mangle]
+pub extern "C" fn relocate_package_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: RelocatePackageRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+    let class_data = match decode_base64(&request.base64_content) {
+        Ok(data) => data,
+        Err(e) => return store_error(request.file_path, format!("Base64 decode error: {}", e)),
+    };
+
+    match relocate::relocate_package(&class_data, &request.from_prefix, &request.to_prefix) {
+        Ok(data) => store(&ClassBytesResponse {
+            success: true,
+            file_path: request.file_path,
+            base64_content: encode_base64(&data),
+        }),
+        Err(e) => store_error(request.file_path, e),
+    }
+}
+
+/// Disassembles and reassembles a class, and reports which bytes and sections of the result differ.
+#[no_mangle]
+pub extern "C" fn roundtrip_diff_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: RoundtripDiffRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+    let class_data = match decode_base64(&request.base64_content) {
+        Ok(data) => data,
+        Err(e) => return store_error(request.file_path, format!("Base64 decode error: {}", e)),
+    };
+
+    let parse_opts = request.options.parser_options();
+    let opts = request.options.disassembler_options();
+    match diff::roundtrip_diff(&class_data, parse_opts, opts, request.max_ranges.unwrap_or(100)) {
+        Ok(diff) => store(&RoundtripDiffResponse {
+            success: true,
+            file_path: request.file_path,
+            diff,
+        }),
+        Err(e) => store_error(request.file_path, e),
+    }
+}
+
+/// Assembles a minimal class named Synth with a method using the given opcode, for test fixtures.
+#[no_mangle]
+pub extern "C" fn synthesize_opcode_class_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: SynthesizeOpcodeRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+
+    match synth::synthesize(&request.opcode) {
+        Ok(data) => store(&ClassBytesResponse {
+            success: true,
+            file_path: request.file_path,
+            base64_content: encode_base64(&data),
+        }),
+        Err(e) => store_error(request.file_path, e),
+    }
+}
+
+/// Rebuilds a class in a canonical byte form, for content addressed storage.
+#[no_mangle]
+pub extern "C" fn canonicalize_class_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let (request, class_data) = match read_class_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+
+    match edit::canonicalize(&class_data) {
+        Ok(data) => store(&ClassBytesResponse {
+            success: true,
+            file_path: request.file_path,
+            base64_content: encode_base64(&data),
+        }),
+        Err(e) => store_error(request.file_path, e),
+    }
+}
+
+/// Adds, replaces or removes the SourceFile attribute of a class without reassembling it.
+#[no_mangle]
+pub extern "C" fn set_source_file_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: SetSourceFileRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+    let class_data = match decode_base64(&request.base64_content) {
+        Ok(data) => data,
+        Err(e) => return store_error(request.file_path, format!("Base64 decode error: {}", e)),
+    };
+
+    match edit::set_source_file(&class_data, &request.source_file) {
+        Ok(data) => store(&ClassBytesResponse {
+            success: true,
+            file_path: request.file_path,
+            base64_content: encode_base64(&data),
+        }),
+        Err(e) => store_error(request.file_path, e),
+    }
+}
+
+/// Strips attributes which aren't needed at runtime to make a class as small as possible.
+#[no_mangle]
+pub extern "C" fn minify_class_json(json_ptr: *const u8, json_len: usize) -> i32 {
+    let request: MinifyRequest = match read_request(json_ptr, json_len) {
+        Ok(v) => v,
+        Err(code_or_response) => return code_or_response,
+    };
+    let class_data = match decode_base64(&request.base64_content) {
+        Ok(data) => data,
+        Err(e) => return store_error(request.file_path, format!("Base64 decode error: {}", e)),
+    };
+
+    match edit::minify(&class_data, &request.options) {
+        Ok(data) => store(&MinifyResponse {
+            success: true,
+            file_path: request.file_path,
+            base64_content: encode_base64(&data),
+            original_size: class_data.len(),
+            minified_size: data.len(),
+            bytes_saved: class_data.len() as i64 - data.len() as i64,
+        }),
+        Err(e) => store_error(request.file_path, e),
+    }
+}
+
+/// Stores the table of classfile major versions, their Java releases and how well each is supported.
+#[no_mangle]
+pub extern "C" fn version_info_json() -> i32 {
+    store(&VersionInfoResponse {
+        success: true,
+        max_supported_major: versions::MAX_SUPPORTED_MAJOR,
+        versions: versions::version_table(),
+    })
+}
+
+/// Stores which attributes survive disassembly and reassembly in a readable form, and which only as bytes.
+#[no_mangle]
+pub extern "C" fn supported_attributes() -> i32 {
+    use krakatau_lib::classfile::attrs::{OPAQUE_ATTRIBUTES, PARSED_ATTRIBUTES};
+    store(&SupportedAttributesResponse {
+        success: true,
+        fully_supported: PARSED_ATTRIBUTES.to_vec(),
+        opaque: OPAQUE_ATTRIBUTES.to_vec(),
+    })
+}
+
+/// Stores a JSON array describing each exported function: its name, parameters, return type and
+/// what the return value means.
+#[no_mangle]
+pub extern "C" fn abi_json() -> i32 {
+    store(&abi::EXPORTS)
+}
+
+/// Disassembles raw (non-JSON, non-base64) class bytes `iterations` times, discarding the output,
+/// for timing by the host. Returns the total number of output bytes produced, -1 for null input and
+/// -2 if the class fails to disassemble.
+#[no_mangle]
+pub extern "C" fn benchmark_decompile(ptr: *const u8, len: usize, iterations: u32) -> i64 {
+    if ptr.is_null() || len == 0 {
+        return -1;
+    }
+    let class_data = unsafe { std::slice::from_raw_parts(ptr, len) };
+    let opts = krakatau_lib::DisassemblerOptions::default();
+
+    SCRATCH.with(|scratch| {
+        let mut out = scratch.borrow_mut();
+        let mut total = 0;
+        for _ in 0..iterations {
+            out.clear();
+            if krakatau_lib::disassemble_into(class_data, PARSE_OPTS, opts.clone(), &mut out).is_err() {
+                return -2;
+            }
+            total += out.len() as i64;
+        }
+        total
+    })
+}
+
+/// Assembles raw (non-JSON) source, passing each class to the host's `write_chunk` import as soon as
+/// it's assembled instead of collecting them into a response. Each class is written as one record: its
+/// length as a little endian u32, followed by its bytes. Returns the number of records written, -1 for
+/// null or non-UTF-8 input, -2 if assembly fails or the source declares no classes (storing the error
+/// as the response) and -3 if `write_chunk` reports a failure. Records written before a failure stay
+/// written.
+#[no_mangle]
+pub extern "C" fn assemble_streaming(src_ptr: *const u8, src_len: usize) -> i32 {
+    if src_ptr.is_null() || src_len == 0 {
+        free_response();
+        return -1;
+    }
+    let source = match std::str::from_utf8(unsafe { std::slice::from_raw_parts(src_ptr, src_len) }) {
+        Ok(source) => source,
+        Err(_) => {
+            free_response();
+            return -1;
+        }
+    };
+
+    let mut count = 0;
+    let mut write_failed = false;
+    let mut record = Vec::new();
+    let result = assemble_source_each(source, krakatau_lib::AssemblerOptions {}, |_, data| {
+        record.clear();
+        record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        record.extend_from_slice(&data);
+        if !host_write_chunk(&record) {
+            write_failed = true;
+            return false;
+        }
+        count += 1;
+        true
+    });
+    match result {
+        Ok(()) if write_failed => -3,
+        Ok(()) => count,
+        Err(AssembleFailure::EmptySource) => {
+            store_error(unknown_path(), EMPTY_SOURCE_ERROR.to_string());
+            -2
+        }
+        Err(AssembleFailure::Assembly(err)) => {
+            store_error(unknown_path(), format!("Assembly error: {:?}", err));
+            -2
+        }
+    }
+}
+
+/// Largest `output_alignment` accepted. Anything bigger would mostly be padding.
+const MAX_OUTPUT_ALIGNMENT: usize = 4096;
+
+/// Pads a class to a multiple of `alignment` bytes by adding a zero filled `Padding` attribute, and
+/// a Utf8 constant for its name. Classes which are already aligned are left as they are.
+fn pad_class(data: &[u8], alignment: usize) -> Result<Vec<u8>, String> {
+    if data.len() % alignment == 0 {
+        return Ok(data.to_vec());
+    }
+    let raw = RawClass::parse(data).map_err(|e| format!("Class parse error: {}", e))?;
+    if raw.cp_count == u16::MAX {
+        return Err("Constant pool is too full to add padding".to_string());
+    }
+    if raw.attrs.len() >= u16::MAX as usize {
+        return Err("Class has too many attributes to add padding".to_string());
+    }
+    let mut cp = raw.cp.to_vec();
+    cp.push(1);
+    cp.extend_from_slice(&(b"Padding".len() as u16).to_be_bytes());
+    cp.extend_from_slice(b"Padding");
+
+    // The new constant, then the attribute's name and length
+    let unpadded = data.len() + (cp.len() - raw.cp.len()) + 6;
+    let zeros = vec![0; unpadded.wrapping_neg() & (alignment - 1)];
+    let mut attrs = raw.attrs.clone();
+    attrs.push(raw_class::RawAttr {
+        name: raw.cp_count,
+        data: &zeros,
+    });
+    Ok(RawClass {
+        cp_count: raw.cp_count + 1,
+        cp: &cp,
+        attrs,
+        ..raw
+    }
+    .to_bytes())
+}
+
+/// Checks an assembled class against the optional size limits of an assemble request.
+fn check_size_limits(class: &krakatau_lib::classfile::parse::Class, request: &AssembleRequest) -> Result<(), String> {
+    let class_name = metadata::cls(&class.cp, class.this).unwrap_or_default();
+
+    if let Some(max) = request.max_method_code {
+        if let Some(largest) = analysis::code_sizes(class).into_iter().next().filter(|s| s.code_length > max) {
+            return Err(format!(
+                "Method {} of {} has {} bytes of code, exceeding max_method_code of {}",
+                largest.method, class_name, largest.code_length, max
+            ));
+        }
+    }
+    if let Some(max) = request.max_members {
+        for (kind, count) in [("methods", class.methods.len()), ("fields", class.fields.len())] {
+            if count > max as usize {
+                return Err(format!("{} declares {} {}, exceeding max_members of {}", class_name, count, kind, max));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Splits a line of source into tokens at whitespace, keeping quoted strings whole and stopping at
+/// a comment.
+fn source_tokens(line: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c.is_whitespace() => {
+                if let Some(s) = start.take() {
+                    tokens.push(&line[s..i]);
+                }
+            }
+            None if c == ';' && start.is_none() => return tokens,
+            None => {
+                start.get_or_insert(i);
+                if c == '"' || c == '\'' {
+                    quote = Some(c);
+                }
+            }
+        }
+    }
+    tokens.extend(start.map(|s| &line[s..]));
+    tokens
+}
+
+/// Whether a name token from the source, which may be quoted, is `name`.
+fn token_is(token: &str, name: &str) -> bool {
+    let body = token.strip_prefix('b').unwrap_or(token);
+    let quoted = ['"', '\''].iter().any(|&q| body.len() >= 2 && body.starts_with(q) && body.ends_with(q));
+    if quoted {
+        krakatau_lib::unescape(token).is_ok_and(|bytes| bytes == krakatau_lib::encode_mutf8(name))
+    } else {
+        token == name
+    }
+}
+
+/// Maps an offset in source rewritten by `apply_method_options` back to the original source. Offsets
+/// inside inserted text map to where it was inserted.
+fn original_offset(insertions: &[(usize, usize)], pos: usize) -> usize {
+    let mut shift = 0;
+    for &(at, len) in insertions {
+        if pos < at + shift {
+            break;
+        }
+        if pos < at + shift + len {
+            return at;
+        }
+        shift += len;
+    }
+    pos - shift
+}
+
+/// Rewrites the source of the methods that `methods` applies to. Currently, this adds a
+/// `.linenumbertable` to the end of the code of methods with a `line_number_map`. Also returns
+/// where text was inserted, as (offset in the original source, length) pairs, so that error
+/// locations can be mapped back.
+fn apply_method_options<'a>(
+    source: &'a str,
+    methods: &[MethodAssembleOptions],
+) -> Result<(Cow<'a, str>, Vec<(usize, usize)>), String> {
+    if methods.iter().all(|m| m.line_number_map.is_none()) {
+        return Ok((Cow::Borrowed(source), Vec::new()));
+    }
+    // Labels are copied into the source as is, so anything else could change what it means
+    let is_label = |s: &str| {
+        s.strip_prefix('L')
+            .is_some_and(|rest| !rest.is_empty() && rest.chars().all(|c| c.is_alphanumeric() || c == '_'))
+    };
+    for m in methods {
+        if let Some(entry) = m.line_number_map.iter().flatten().find(|entry| !is_label(&entry.label)) {
+            return Err(format!("Invalid label {:?} in line_number_map of method {}", entry.label, m.method_name));
+        }
+    }
+
+    let mut out = String::with_capacity(source.len() + 1000);
+    let mut insertions = Vec::new();
+    let mut current = None;
+    let mut matched = vec![false; methods.len()];
+    for line in source.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with(".method ") {
+            // .method <flags> name : descriptor
+            let tokens = source_tokens(trimmed);
+            current = tokens.iter().position(|&t| t == ":").filter(|&i| i > 1 && i + 1 < tokens.len()).and_then(|i| {
+                methods.iter().position(|m| {
+                    token_is(tokens[i - 1], &m.method_name)
+                        && m.method_descriptor.iter().all(|desc| token_is(tokens[i + 1], desc))
+                })
+            });
+        } else if source_tokens(trimmed) == [".end", "code"] {
+            if let Some(i) = current.take() {
+                if let Some(map) = &methods[i].line_number_map {
+                    let before = out.len();
+                    out.push_str("        .linenumbertable\n");
+                    for entry in map {
+                        out.push_str(&format!("            {} {}\n", entry.label, entry.line));
+                    }
+                    out.push_str("        .end linenumbertable\n");
+                    // Every line so far has been copied over, so this is the line's original offset
+                    let at = line.as_ptr() as usize - source.as_ptr() as usize;
+                    insertions.push((at, out.len() - before));
+                }
+                matched[i] = true;
+            }
+        }
+        out.push_str(line);
+    }
+
+    if let Some(i) = (0..methods.len()).find(|&i| !matched[i] && methods[i].line_number_map.is_some()) {
+        let m = &methods[i];
+        return Err(format!(
+            "No method {}{} with code in source",
+            m.method_name,
+            m.method_descriptor.as_deref().unwrap_or("")
+        ));
+    }
+    Ok((Cow::Owned(out), insertions))
+}
+
+/// Breaks lines longer than `columns` at spaces, continuing them on lines indented four spaces further.
+/// String literals and trailing comments are never split, so lines made of long tokens stay long.
+fn wrap_lines(text: &str, columns: usize) -> String {
+    let mut out = String::with_capacity(text.len() + text.len() / 8);
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches('\n');
+        if content.chars().count() <= columns {
+            out.push_str(line);
+            continue;
+        }
+
+        let indent = &content[..content.len() - content.trim_start().len()];
+        // Spaces where the line may be broken, followed by the end of the line
+        let mut breaks = Vec::new();
+        let mut quote = None;
+        let mut escaped = false;
+        for (i, c) in content.char_indices().skip(indent.len()) {
+            match quote {
+                Some(_) if escaped => escaped = false,
+                Some(_) if c == '\\' => escaped = true,
+                Some(q) if c == q => quote = None,
+                Some(_) => {}
+                None if c == '"' || c == '\'' => quote = Some(c),
+                None if c == ' ' => breaks.push(i),
+                // Descriptors contain semicolons too, but comments start a token
+                None if c == ';' && (i == indent.len() || breaks.last().map(|&b| b + 1) == Some(i)) => break,
+                None => {}
+            }
+        }
+        breaks.push(content.len());
+
+        let mut start = 0;
+        let mut width = 0;
+        let mut last = None;
+        for &pos in &breaks {
+            let piece = content[start..pos].chars().count();
+            if let Some(last) = last.filter(|_| width + piece > columns) {
+                out.push_str(&content[start..last]);
+                out.push('\n');
+                out.push_str(indent);
+                out.push_str("    ");
+                start = last + 1;
+                width = indent.len() + 4;
+            }
+            last = Some(pos);
+        }
+        out.push_str(&content[start..]);
+        out.push('\n');
+    }
+    out
+}
+
+/// Problems with an assembled class which the assembler itself doesn't check for.
+fn assembly_warnings(class: &krakatau_lib::classfile::parse::Class) -> Vec<String> {
+    let class_name = metadata::cls(&class.cp, class.this).unwrap_or_default();
+    analysis::missing_frames(class)
+        .into_iter()
+        .map(|sig| {
+            format!(
+                "Method {} of {} has jumps but no stack map frames, which version {} classes require",
+                sig, class_name, class.version.0
+            )
+        })
+        .collect()
+}
+
+/// Problems with a parsed class which the disassembler tolerates, but the JVM wouldn't.
+fn consistency_warnings(class: &krakatau_lib::classfile::parse::Class) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let class_name = metadata::cls(&class.cp, class.this).unwrap_or_else(|| "<unnamed class>".to_owned());
+    // module-info classes are required to have no super class
+    let is_module = class.access & 0x8000 != 0;
+    if class.super_ == 0 && class_name != "java/lang/Object" && !is_module {
+        warnings.push(format!("{} has no super class, which only java/lang/Object may omit", class_name));
+    }
+
+    for (kind, members, sep) in [("field", &class.fields, " "), ("method", &class.methods, "")] {
+        let mut seen = HashMap::new();
+        for m in members {
+            // Compare the raw bytes, since distinct names needn't be valid Modified UTF-8
+            let key = (class.cp.utf8(m.name), class.cp.utf8(m.desc));
+            let count = seen.entry(key).or_insert(0);
+            *count += 1;
+            // Report each duplicated signature once, however many times it's declared
+            if *count == 2 {
+                let text = |bytes: Option<&[u8]>| {
+                    let bytes = bytes.unwrap_or_default();
+                    krakatau_lib::parse_utf8(bytes).unwrap_or_else(|| String::from_utf8_lossy(bytes).into_owned())
+                };
+                let sig = format!("{}{}{}", text(key.0), sep, text(key.1));
+                warnings.push(format!("{} declares {} {} more than once", class_name, kind, sig));
+            }
+        }
+    }
+    warnings
+}
+
+/// Each use of a forbidden opcode, with the method and bytecode offset. Fails if an opcode name is unknown.
+fn forbidden_opcode_warnings(
+    class: &krakatau_lib::classfile::parse::Class,
+    forbidden: &[String],
+) -> Result<Vec<String>, String> {
+    use krakatau_lib::classfile::code::{Instr, MNEMONICS};
+
+    let mut opcodes = Vec::with_capacity(forbidden.len());
+    for name in forbidden {
+        let lower = name.to_ascii_lowercase();
+        match MNEMONICS.iter().position(|&m| m == lower) {
+            Some(opcode) => opcodes.push(opcode as u8),
+            None => return Err(format!("Unknown opcode {} in forbidden_opcodes", name)),
+        }
+    }
+    if opcodes.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let class_name = metadata::cls(&class.cp, class.this).unwrap_or_else(|| "<unnamed class>".to_owned());
+    let mut warnings = Vec::new();
+    for m in &class.methods {
+        let Some(code) = analysis::method_code(m) else {
+            continue;
+        };
+        for (pos, instr) in &code.bytecode.0 {
+            let modified = match instr {
+                Instr::Wide(w) => Some(w.opcode()),
+                _ => None,
+            };
+            for opcode in [Some(instr.opcode()), modified].into_iter().flatten() {
+                if opcodes.contains(&opcode) {
+                    warnings.push(format!(
+                        "Method {} of {} uses forbidden opcode {} at offset {}",
+                        analysis::method_sig(&class.cp, m),
+                        class_name,
+                        MNEMONICS[opcode as usize],
+                        pos.offset()
+                    ));
+                }
+            }
+        }
+    }
+    Ok(warnings)
+}
+
+/// Validates a raw (non-JSON) base64 string without decoding it. Returns the decoded length, or
+/// -2 for an invalid character, -3 for invalid padding and -4 for an impossible length.
+#[no_mangle]
+pub extern "C" fn validate_base64(ptr: *const u8, len: usize) -> i32 {
+    if ptr.is_null() {
+        return -1;
+    }
+    let input = unsafe { std::slice::from_raw_parts(ptr, len) };
+    let input = match std::str::from_utf8(input) {
+        Ok(input) => input,
+        Err(_) => return Base64Error::InvalidCharacter.code(),
+    };
+    match check_base64(input) {
+        Ok((_, len)) => len as i32,
+        Err(e) => e.code(),
+    }
+}
+
+/// Length of the stored response, or 0 if there is none.
+#[no_mangle]
+pub extern "C" fn get_response_length() -> i32 {
+    unsafe {
+        if RESPONSE_PTR.is_null() { 0 } else { RESPONSE_LEN as i32 }
+    }
+}
+
+/// Start of the stored response, or null if there is none.
+#[no_mangle]
+pub extern "C" fn get_response_ptr() -> *const u8 {
+    unsafe {
+        if RESPONSE_LEN == 0 { std::ptr::null() } else { RESPONSE_PTR }
+    }
+}
+
+/// Returns 1 if a response is stored, and 0 if there is none, either because it was freed or
+/// because the last call failed to store one.
+#[no_mangle]
+pub extern "C" fn has_response() -> i32 {
+    unsafe { (!RESPONSE_PTR.is_null() && RESPONSE_LEN != 0) as i32 }
+}
+
+/// Copies up to `max_len` bytes of the stored response, starting at `offset`, to the buffer returned
+/// by `get_response_chunk_ptr`, for hosts which read responses through a fixed size window. Returns
+/// the number of bytes copied (zero at the end of the response), or -1 if `offset` is past the end.
+#[no_mangle]
+pub extern "C" fn get_response_chunk(offset: usize, max_len: usize) -> i32 {
+    let response: &[u8] = unsafe {
+        if RESPONSE_PTR.is_null() {
+            &[]
+        } else {
+            std::slice::from_raw_parts(RESPONSE_PTR, RESPONSE_LEN)
+        }
+    };
+    if offset > response.len() {
+        return -1;
+    }
+    let chunk = &response[offset..][..max_len.min(response.len() - offset)];
+    CHUNK.with(|buf| {
+        let mut buf = buf.borrow_mut();
+        buf.clear();
+        buf.extend_from_slice(chunk);
+    });
+    chunk.len() as i32
+}
+
+/// Start of the bytes copied by the last call to `get_response_chunk`.
+#[no_mangle]
+pub extern "C" fn get_response_chunk_ptr() -> *const u8 {
+    CHUNK.with(|buf| buf.borrow().as_ptr())
+}
+
+fn store_response(response: DecompileResponse) -> i32 {
+    // Serialize response to JSON
+    let json_string = match serde_json::to_string(&response) {
+        Ok(json) => json,
+        Err(e) => {
+            let fallback = DecompileResponse::error(
+                "unknown".to_string(),
+                format!("JSON serialization error: {}", e),
+            );
+            serde_json::to_string(&fallback).unwrap_or_default()
+        }
+    };
+
+    store_json_response(json_string)
+}
+
+fn store_assemble_response(response: AssembleResponse) -> i32 {
+    // Serialize response to JSON
+    let json_string = match serde_json::to_string(&response) {
+        Ok(json) => json,
+        Err(e) => {
+            let fallback = AssembleResponse::error(
+                "unknown".to_string(),
+                format!("JSON serialization error: {}", e),
+            );
+            serde_json::to_string(&fallback).unwrap_or_default()
+        }
+    };
+
+    store_json_response(json_string)
+}
+
+/// Reads and deserializes a JSON request, storing an error response on failure. The `Err` value is
+/// what the calling export should return.
+fn read_request<T: DeserializeOwned>(json_ptr: *const u8, json_len: usize) -> Result<T, i32> {
+    if json_ptr.is_null() || json_len == 0 {
+        free_response();
+        return Err(-1); // Error: null or empty input
+    }
+
+    let json_data = unsafe { std::slice::from_raw_parts(json_ptr, json_len) };
+    serde_json::from_slice(json_data)
+        .map_err(|e| store_error(request_path(json_data), format!("JSON parse error: {}", e)))
+}
+
+fn read_class_request(json_ptr: *const u8, json_len: usize) -> Result<(ClassRequest, Vec<u8>), i32> {
+    let request: ClassRequest = read_request(json_ptr, json_len)?;
+    match decode_base64(&request.base64_content) {
+        Ok(data) => Ok((request, data)),
+        Err(e) => Err(store_error(request.file_path, format!("Base64 decode error: {}", e))),
+    }
+}
+
+fn store_error(file_path: String, error: String) -> i32 {
+    store(&ErrorResponse {
+        success: false,
+        file_path,
+        error,
+    })
+}
+
+fn store<T: Serialize>(response: &T) -> i32 {
+    let json_string = match serde_json::to_string(response) {
+        Ok(json) => json,
+        Err(e) => {
+            let fallback = ErrorResponse {
+                success: false,
+                file_path: unknown_path(),
+                error: format!("JSON serialization error: {}", e),
+            };
+            serde_json::to_string(&fallback).unwrap_or_default()
+        }
+    };
+
+    store_json_response(json_string)
+}
+
+fn store_json_response(json_string: String) -> i32 {
+    // Free the previous response first, so it can't be mistaken for the result of a failed call
+    free_response();
+
+    let json_bytes = json_string.into_bytes();
+    let len = json_bytes.len();
+    if len == 0 || len >= MAX_RESPONSE_LEN { return -1; }
+
+    unsafe {
+        let layout = match Layout::array::<u8>(len) { Ok(l) => l, Err(_) => return -1 };
+        let ptr = alloc(layout);
+        if ptr.is_null() { return -1; }
+
+        std::ptr::copy_nonoverlapping(json_bytes.as_ptr(), ptr, len);
+        RESPONSE_PTR = ptr;
+        RESPONSE_LEN = len;
+        len as i32
+    }
+}
+
+/// Frees the stored response. Afterwards `get_response_ptr` returns null and `get_response_length` 0
+/// until the next call stores a response.
+#[no_mangle]
+pub extern "C" fn free_response() {
+    unsafe {
+        if !RESPONSE_PTR.is_null() && RESPONSE_LEN != 0 {
+            if let Some(layout) = Layout::array::<u8>(RESPONSE_LEN).ok() {
+                dealloc(RESPONSE_PTR, layout);
+            }
+        }
+        RESPONSE_PTR = std::ptr::null_mut();
+        RESPONSE_LEN = 0;
+    }
+}
+
+// Inflated content larger than this is rejected, to guard against decompression bombs.
+#[cfg(feature = "gzip")]
+const MAX_INFLATED_SIZE: u64 = 16 * 1024 * 1024;
+
+fn decode_content(data: Vec<u8>, encoding: ContentEncoding) -> Result<Vec<u8>, String> {
+    match encoding {
+        ContentEncoding::Identity => Ok(data),
+        #[cfg(feature = "gzip")]
+        ContentEncoding::Gzip => {
+            use std::io::Read;
+            let mut out = Vec::new();
+            flate2::read::GzDecoder::new(&data[..])
+                .take(MAX_INFLATED_SIZE + 1)
+                .read_to_end(&mut out)
+                .map_err(|e| format!("Gzip decode error: {}", e))?;
+            if out.len() as u64 > MAX_INFLATED_SIZE {
+                return Err(format!("Gzip decode error: content exceeds {} bytes", MAX_INFLATED_SIZE));
+            }
+            Ok(out)
+        }
+        #[cfg(not(feature = "gzip"))]
+        ContentEncoding::Gzip => Err("Gzip content encoding requires the gzip feature".to_string()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Base64Error {
+    InvalidCharacter,
+    InvalidPadding,
+    InvalidLength,
+}
+
+impl Base64Error {
+    /// Negative return code used by `validate_base64`
+    fn code(self) -> i32 {
+        match self {
+            Base64Error::InvalidCharacter => -2,
+            Base64Error::InvalidPadding => -3,
+            Base64Error::InvalidLength => -4,
+        }
+    }
+}
+
+impl std::fmt::Display for Base64Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Base64Error::InvalidCharacter => "Invalid base64 character",
+            Base64Error::InvalidPadding => "Invalid base64 padding",
+            Base64Error::InvalidLength => "Invalid base64 length",
+        })
+    }
+}
+
+fn base64_value(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+/// Checks that `input` is well formed base64 and returns the unpadded data it contains along
+/// with its decoded length. Padding is optional, but must be correct if present.
+fn check_base64(input: &str) -> Result<(&[u8], usize), Base64Error> {
+    let input = input.trim().as_bytes();
+    let data_len = input.iter().position(|&c| c == b'=').unwrap_or(input.len());
+    let (data, padding) = input.split_at(data_len);
+
+    if data.iter().any(|&c| base64_value(c).is_none()) {
+        return Err(Base64Error::InvalidCharacter);
+    }
+    if data.len() % 4 == 1 {
+        return Err(Base64Error::InvalidLength);
+    }
+    if !padding.is_empty() && (padding.iter().any(|&c| c != b'=') || (data.len() + padding.len()) % 4 != 0 || padding.len() > 2) {
+        return Err(Base64Error::InvalidPadding);
+    }
+    Ok((data, data.len() * 3 / 4))
+}
+
+fn decode_base64(input: &str) -> Result<Vec<u8>, Base64Error> {
+    let (input, len) = check_base64(input)?;
+
+    let mut result = Vec::with_capacity(len);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for &c in input {
+        buffer = (buffer << 6) | (base64_value(c).unwrap() as u32);
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            result.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Responses live in process-wide statics, so tests going through the FFI must not interleave
+    static FFI_LOCK: Mutex<()> = Mutex::new(());
+
+    const HELLO: &str = include_str!("../examples/hello.j");
+
+    const LAMBDA: &str = r#"
+.version 52 0
+.class public Foo
+.super java/lang/Object
+
+.method public static main : ([Ljava/lang/String;)V
+    .code stack 1 locals 1
+        invokedynamic InvokeDynamic invokeStatic Method java/lang/invoke/LambdaMetafactory metafactory (Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/invoke/MethodType;Ljava/lang/invoke/MethodType;Ljava/lang/invoke/MethodHandle;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/CallSite; MethodType ()V MethodHandle invokeStatic Method Foo lambda$main$0 ()V MethodType ()V : run ()Ljava/lang/Runnable;
+        invokeinterface InterfaceMethod java/lang/Runnable run ()V 1
+        return
+    .end code
+.end method
+
+.method private static synthetic lambda$main$0 : ()V
+    .code stack 0 locals 0
+        return
+    .end code
+.end method
+.end class
+"#;
+
+    fn call(f: extern "C" fn(*const u8, usize) -> i32, request: serde_json::Value) -> serde_json::Value {
+        let _guard = FFI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let json = request.to_string();
+        read_response(f(json.as_ptr(), json.len()))
+    }
+
+    fn assemble_one(source: &str) -> Vec<u8> {
+        let mut classes = krakatau_lib::assemble(source, AssemblerOptions {}).unwrap();
+        classes.remove(0).1
+    }
+
+    fn read_response(len: i32) -> serde_json::Value {
+        assert!(len >= 0);
+        let resp = unsafe { std::slice::from_raw_parts(get_response_ptr(), get_response_length() as usize) };
+        let resp = serde_json::from_slice(resp).unwrap();
+        free_response();
+        resp
+    }
+
+    fn class_request(f: extern "C" fn(*const u8, usize) -> i32, class: &[u8], extra: serde_json::Value) -> serde_json::Value {
+        let mut request = serde_json::json!({"file_path": "Foo.class", "base64_content": encode_base64(class)});
+        request.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        call(f, request)
+    }
+
+    fn decompile(class: &[u8], extra: serde_json::Value) -> serde_json::Value {
+        class_request(decompile_json, class, extra)
+    }
+
+    #[test]
+    fn test_output_hint_avoids_regrowth() {
+        let class = assemble_one(HELLO);
+        let size = decompile(&class, serde_json::json!({}))["output"].as_str().unwrap().len();
+
+        // Fresh threads get a fresh scratch buffer, so count the regrowths each one sees over a batch
+        let regrowths = |hint: usize| {
+            let class = class.clone();
+            std::thread::spawn(move || {
+                set_output_hint(hint);
+                let mut count = 0;
+                for _ in 0..10 {
+                    let before = SCRATCH.with(|s| s.borrow().capacity());
+                    decompile(&class, serde_json::json!({}));
+                    if SCRATCH.with(|s| s.borrow().capacity()) != before {
+                        count += 1;
+                    }
+                }
+                count
+            })
+            .join()
+            .unwrap()
+        };
+
+        assert!(regrowths(0) > 0);
+        assert_eq!(regrowths(size), 0);
+        assert!(regrowths(MAX_OUTPUT_HINT + 1) > 0);
+    }
+
+    #[test]
+    fn test_inline_indy_targets() {
+        let class = assemble_one(LAMBDA);
+        let out = decompile(&class, serde_json::json!({"inline_indy_targets": true}));
+        let line = out["output"].as_str().unwrap().lines().find(|l| l.contains("invokedynamic")).unwrap();
+        assert!(line.contains("bootstrap invokeStatic java/lang/invoke/LambdaMetafactory.metafactory"));
+        assert!(line.contains("implementation invokeStatic Foo.lambda$main$0()V"));
+
+        let out = decompile(&class, serde_json::json!({}));
+        assert!(!out["output"].as_str().unwrap().contains("implementation"));
+    }
+
+    #[test]
+    fn test_reduce_failure() {
+        let class = assemble_one(
+            r#"
+.class public Foo
+.super java/lang/Object
+.const [30] = Int 5
+
+.method public static good : ()V
+    .code stack 0 locals 0
+        return
+    .end code
+.end method
+
+.method public static bad : ()V
+    .code stack 0 locals 0
+        return
+    .end code
+    .attribute [30] b'\x00'
+.end method
+.end class
+"#,
+        );
+
+        let out = class_request(reduce_failure_json, &class, serde_json::json!({}));
+        assert_eq!(out["success"], true);
+        assert_eq!(out["failure"], "Attribute has invalid name index");
+
+        let reduced = decode_base64(out["reduced_base64"].as_str().unwrap()).unwrap();
+        assert!(reduced.len() < class.len());
+        let raw = RawClass::parse(&reduced).unwrap();
+        assert_eq!(raw.methods.len(), 1);
+        assert_eq!(raw.methods[0].attrs.len(), 1);
+        // The method left is the one with the broken attribute
+        let original = RawClass::parse(&class).unwrap();
+        assert_eq!(raw.methods[0].name, original.methods[1].name);
+        assert_eq!(raw.methods[0].attrs[0].data, b"\x00");
+        assert_eq!(out["truncated"], false);
+
+        let out = class_request(reduce_failure_json, &assemble_one(HELLO), serde_json::json!({}));
+        assert_eq!(out["success"], false);
+    }
+
+    #[test]
+    fn test_version_info() {
+        let out = {
+            let _guard = FFI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            read_response(version_info_json())
+        };
+        let max = out["max_supported_major"].as_u64().unwrap();
+        let majors: Vec<_> = out["versions"].as_array().unwrap().iter().map(|v| v["major"].as_u64().unwrap()).collect();
+        for major in 52..=max {
+            assert!(majors.contains(&major));
+        }
+        let java8 = out["versions"].as_array().unwrap().iter().find(|v| v["major"] == 52).unwrap();
+        assert_eq!(java8["java"], "8");
+        assert_eq!(java8["status"], "supported");
+    }
+
+    #[test]
+    fn test_annotate_attributes() {
+        let class = assemble_one(HELLO);
+        let code_len = RawClass::parse(&class).unwrap().methods[0].attrs[0].data.len();
+
+        let out = decompile(&class, serde_json::json!({"annotate_attributes": true}));
+        let output = out["output"].as_str().unwrap();
+        assert!(output.contains(&format!("; Code, {} bytes\n", code_len)));
+
+        // Comments don't change what the output assembles to
+        let plain = decompile(&class, serde_json::json!({}));
+        assert_eq!(assemble_one(output), assemble_one(plain["output"].as_str().unwrap()));
+    }
+
+    #[test]
+    fn test_patch_method() {
+        let class = assemble_one(HELLO);
+        let patch = |source: &str| class_request(patch_method_json, &class, serde_json::json!({"method_source": source}));
+        let method = |body: &str| {
+            format!(
+                ".method public static {} : ([Ljava/lang/String;)V\n    .code stack 2 locals 1\n        getstatic Field java/lang/System out Ljava/io/PrintStream;\n        ldc \"Patched!\"\n        invokevirtual Method java/io/PrintStream println (Ljava/lang/Object;)V\n        return\n    .end code\n.end method\n",
+                body
+            )
+        };
+
+        let out = patch(&method("main"));
+        assert_eq!(out["success"], true);
+        let patched = decode_base64(out["base64_content"].as_str().unwrap()).unwrap();
+        let output = decompile(&patched, serde_json::json!({}))["output"].as_str().unwrap().to_string();
+        assert!(output.contains("Patched!"));
+        assert!(!output.contains("Hello World!"));
+        assert_eq!(output.matches(".method").count(), 1);
+
+        let out = patch(&method("other"));
+        assert_eq!(out["success"], false);
+        assert!(out["error"].as_str().unwrap().contains("No method"));
+
+        let out = patch(&format!("{}{}", method("main"), method("other")));
+        assert_eq!(out["success"], false);
+        assert!(out["error"].as_str().unwrap().contains("exactly one method"));
+    }
+
+    fn make_jar(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        use std::io::Write;
+        let mut zw = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, data) in entries {
+            zw.start_file(*name, zip::write::FileOptions::default()).unwrap();
+            zw.write_all(data).unwrap();
+        }
+        zw.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_jar_dedupe_identical() {
+        let class = assemble_one(HELLO);
+        let jar = make_jar(&[("Foo.class", &class), ("shaded/Foo.class", &class), ("META-INF/MANIFEST.MF", b"")]);
+
+        let out = class_request(decompile_jar_json, &jar, serde_json::json!({"dedupe_identical": true}));
+        assert_eq!(out["classes"].as_array().unwrap().len(), 1);
+        assert!(out["classes"][0]["output"].as_str().unwrap().contains("Hello World!"));
+        assert_eq!(out["alias_groups"], serde_json::json!([["Foo.class", "shaded/Foo.class"]]));
+
+        let out = class_request(decompile_jar_json, &jar, serde_json::json!({}));
+        assert_eq!(out["classes"].as_array().unwrap().len(), 2);
+        assert_eq!(out["alias_groups"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_assemble_fragments() {
+        let header = ".class public Foo\n.super java/lang/Object\n";
+        let good = ".method public static a : ()V\n    .code stack 0 locals 0\n        return\n    .end code\n.end method\n";
+        let bad = ".method public static b : ()V\n    .code stack 0 locals 0\n        retrun\n    .end code\n.end method\n.end class\n";
+        let fragments = |last: &str| {
+            serde_json::json!({"fragments": [
+                {"name": "header", "source_fragment": header},
+                {"name": "a", "source_fragment": good},
+                {"name": "b", "source_fragment": last},
+            ]})
+        };
+
+        let out = call(assemble_fragments_json, fragments(&bad.replace("retrun", "return")));
+        assert_eq!(out["success"], true);
+        assert_eq!(out["class_files"][0]["name"], "Foo");
+
+        let out = call(assemble_fragments_json, fragments(bad));
+        assert_eq!(out["success"], false);
+        assert!(out["error"].as_str().unwrap().starts_with("Assembly error: b:3: "));
+    }
+
+    #[test]
+    fn test_layout() {
+        let class = assemble_one(HELLO);
+        let out = class_request(layout_json, &class, serde_json::json!({}));
+        let sections = out["sections"].as_array().unwrap();
+        assert_eq!(sections[0], serde_json::json!({"name": "magic", "start": 0, "length": 4}));
+        let pool = sections.iter().find(|s| s["name"] == "constant_pool").unwrap();
+        assert_eq!(pool["start"], 8);
+
+        // Sections are contiguous and cover the whole class
+        let mut end = 0;
+        for s in sections {
+            assert_eq!(s["start"].as_u64().unwrap(), end);
+            end += s["length"].as_u64().unwrap();
+        }
+        assert_eq!(end, class.len() as u64);
+    }
+
+    #[test]
+    fn test_roundtrip_lossy() {
+        let class = assemble_one(".class public Foo\n.super java/lang/Object\n.sourcefile \"Foo.java\"\n.end class\n");
+
+        let out = decompile(&class, serde_json::json!({"roundtrip": true}));
+        assert_eq!(out["roundtrip_lossy"], false);
+        assert_eq!(out["reasons"], serde_json::json!([]));
+        assert!(out["output"].as_str().unwrap().contains(".sourcefile"));
+
+        let out = decompile(&class, serde_json::json!({"roundtrip": true, "strip_debug": true}));
+        assert_eq!(out["roundtrip_lossy"], true);
+        assert_eq!(out["reasons"], serde_json::json!(["debug attributes are stripped"]));
+        assert!(!out["output"].as_str().unwrap().contains(".sourcefile"));
+    }
+
+    #[test]
+    fn test_jar_max_classes() {
+        let class = assemble_one(HELLO);
+        let jar = make_jar(&[("A.class", &class), ("B.class", &class), ("C.class", &class)]);
+
+        let out = class_request(decompile_jar_json, &jar, serde_json::json!({"max_classes": 2}));
+        assert_eq!(out["classes"].as_array().unwrap().len(), 2);
+        assert_eq!(out["truncated"], "class limit reached, 2 processed of 3");
+
+        let out = class_request(decompile_jar_json, &jar, serde_json::json!({"max_classes": 3}));
+        assert_eq!(out["classes"].as_array().unwrap().len(), 3);
+        assert_eq!(out["truncated"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_enclosing_method() {
+        let anon = |enclosing: &str| {
+            assemble_one(&format!(
+                ".class Outer$1\n.super java/lang/Object\n.enclosing method {}\n.end class\n",
+                enclosing
+            ))
+        };
+
+        // Defined inside Outer.run()
+        let out = class_request(metadata_json, &anon("Outer run ()V"), serde_json::json!({}));
+        assert_eq!(out["class_name"], "Outer$1");
+        assert_eq!(
+            out["enclosing_method"],
+            serde_json::json!({"class": "Outer", "name": "run", "descriptor": "()V"})
+        );
+
+        // Defined in a field initializer, so there is no enclosing method
+        let out = class_request(metadata_json, &anon("Outer [0]"), serde_json::json!({}));
+        assert_eq!(
+            out["enclosing_method"],
+            serde_json::json!({"class": "Outer", "name": null, "descriptor": null})
+        );
+
+        let out = class_request(metadata_json, &assemble_one(HELLO), serde_json::json!({}));
+        assert_eq!(out["enclosing_method"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_include_deprecation() {
+        let class = assemble_one(
+            r#"
+.class public Foo
+.super java/lang/Object
+
+.method public static old : ()V
+    .deprecated
+    .runtime visible annotations
+        .annotation Ljava/lang/Deprecated;
+            since = string "9"
+            forRemoval = boolean 1
+        .end annotation
+    .end runtime
+    .code stack 0 locals 0
+        return
+    .end code
+.end method
+
+.method public static current : ()V
+    .code stack 0 locals 0
+        return
+    .end code
+.end method
+.end class
+"#,
+        );
+
+        let out = class_request(metadata_json, &class, serde_json::json!({"include_deprecation": true}));
+        assert_eq!(
+            out["methods"][0],
+            serde_json::json!({"name": "old", "descriptor": "()V", "deprecated": true, "since": "9", "for_removal": true})
+        );
+        assert_eq!(out["methods"][1]["deprecated"], false);
+        assert_eq!(out["methods"][1]["since"], serde_json::Value::Null);
+
+        let out = class_request(metadata_json, &class, serde_json::json!({}));
+        assert!(out["methods"][0].get("deprecated").is_none());
+    }
+
+    #[test]
+    fn test_assemble_session() {
+        let class = |name: &str, msg: &str| {
+            HELLO.replace("class public Foo", &format!("class public {}", name)).replace("Hello World!", msg)
+        };
+        let update = |id: i32, name: &str, source: String| {
+            call(assemble_session_update, serde_json::json!({"session_id": id, "class_name": name, "source": source}))
+        };
+        let build = |id: i32| {
+            let _guard = FFI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            read_response(assemble_session_build(id))
+        };
+
+        let id = assemble_session_begin();
+        assert_eq!(update(id, "A", class("A", "a"))["success"], true);
+        update(id, "B", class("B", "b"));
+        let out = build(id);
+        assert_eq!(out["rebuilt"], serde_json::json!(["A", "B"]));
+        assert_eq!(out["assemble_count"], 2);
+
+        // Only the changed class is reassembled, but every class is returned
+        update(id, "B", class("B", "changed"));
+        update(id, "A", class("A", "a"));
+        let out = build(id);
+        assert_eq!(out["rebuilt"], serde_json::json!(["B"]));
+        assert_eq!(out["assemble_count"], 3);
+        let names: Vec<_> = out["class_files"].as_array().unwrap().iter().map(|c| c["name"].clone()).collect();
+        assert_eq!(names, ["A", "B"]);
+
+        assert_eq!(build(id)["assemble_count"], 3);
+        assert_eq!(assemble_session_end(id), 1);
+        assert_eq!(build(id)["success"], false);
+        let out = update(id, "A", class("A", "a"));
+        assert_eq!(out["success"], false);
+        assert_eq!(out["file_path"], "unknown");
+    }
+
+    #[test]
+    fn test_roundtrip_preserves_pool_order() {
+        // Pin constants to slots the assembler wouldn't choose itself, with a gap and an unused entry
+        let source = HELLO.replace(
+            ".super java/lang/Object \n",
+            ".super java/lang/Object \n.const [40] = String \"Hello World!\"\n.const [2] = Utf8 unused\n.const [25] = Long 7\n",
+        );
+        let class = assemble_one(&source);
+        assert_eq!(RawClass::parse(&class).unwrap().cp_count, 41);
+
+        let out = decompile(&class, serde_json::json!({"roundtrip": true}));
+        let output = out["output"].as_str().unwrap();
+        assert!(output.contains(".const [2] = "));
+        assert_eq!(assemble_one(output), class);
+    }
+
+    #[test]
+    fn test_validate_base64() {
+        let validate = |s: &str| validate_base64(s.as_ptr(), s.len());
+        assert_eq!(validate(""), 0);
+        assert_eq!(validate("TWFu"), 3);
+        assert_eq!(validate("TWE="), 2);
+        assert_eq!(validate("TWE"), 2);
+        assert_eq!(validate("TQ=="), 1);
+        assert_eq!(validate(&encode_base64(&[7; 100])), 100);
+
+        assert_eq!(validate("TW!u"), -2);
+        assert_eq!(validate("TQ=x"), -3);
+        assert_eq!(validate("TW=u"), -3);
+        assert_eq!(validate("TQ="), -3);
+        assert_eq!(validate("TWFu="), -3);
+        assert_eq!(validate("TWFuT"), -4);
+        assert_eq!(validate_base64(std::ptr::null(), 0), -1);
+
+        assert_eq!(decode_base64("TWE=").unwrap(), b"Ma");
+        assert_eq!(decode_base64("TW!u"), Err(Base64Error::InvalidCharacter));
+    }
+
+    #[test]
+    fn test_instructions() {
+        let class = assemble_one(HELLO);
+        let request = |name: &str| {
+            class_request(
+                instructions_json,
+                &class,
+                serde_json::json!({"method_name": name, "method_descriptor": "([Ljava/lang/String;)V"}),
+            )
+        };
+
+        let out = request("main");
+        let instructions = out["instructions"].as_array().unwrap();
+        assert_eq!(instructions[0]["offset"], 0);
+        assert_eq!(instructions[0]["opcode"], "getstatic");
+        assert_eq!(instructions[0]["wide"], false);
+        let field = &instructions[0]["operands"][0];
+        assert_eq!(field["kind"], "constant");
+        assert_eq!(field["constant"]["tag"], "Fieldref");
+        assert_eq!(field["constant"]["class"], "java/lang/System");
+        assert_eq!(field["constant"]["name"], "out");
+        assert_eq!(instructions[1]["offset"], 3);
+        assert_eq!(instructions[1]["opcode"], "ldc");
+        assert_eq!(instructions[1]["operands"][0]["constant"]["value"], "Hello World!");
+        assert_eq!(instructions.last().unwrap()["opcode"], "return");
+        assert_eq!(instructions.last().unwrap()["operands"], serde_json::json!([]));
+
+        let out = request("missing");
+        assert_eq!(out["success"], false);
+
+        // Wide instructions report the instruction they modify, and switches list every case
+        let class = assemble_one(
+            ".class public Foo\n.super java/lang/Object\n.method public static f : (I)V\n    .code stack 1 locals 400\n\
+             L0:     wide iinc 300 -1000\n        wide iload 299\n        tableswitch 1\n            L1\n            L2\n\
+                     default : L2\n\
+             L1:     iload_0\n        lookupswitch\n            5 : L2\n            default : L2\n\
+             L2:     return\n        wide ret 301\n    .end code\n.end method\n.end class\n",
+        );
+        let request = serde_json::json!({"method_name": "f", "method_descriptor": "(I)V"});
+        let out = class_request(instructions_json, &class, request);
+        let instructions = out["instructions"].as_array().unwrap();
+        assert_eq!(
+            instructions[0],
+            serde_json::json!({"offset": 0, "opcode": "iinc", "wide": true, "operands": [
+                {"kind": "local", "index": 300}, {"kind": "int", "value": -1000},
+            ]})
+        );
+        assert_eq!(instructions[1]["opcode"], "iload");
+        assert_eq!(instructions[1]["wide"], true);
+        let (l1, l2) = (&instructions[3]["offset"], &instructions[5]["offset"]);
+        assert_eq!(instructions[2]["opcode"], "tableswitch");
+        assert_eq!(
+            instructions[2]["operands"],
+            serde_json::json!([{
+                "kind": "switch",
+                "default": l2,
+                "cases": [{"key": 1, "target": l1}, {"key": 2, "target": l2}],
+            }])
+        );
+        assert_eq!(instructions[4]["opcode"], "lookupswitch");
+        assert_eq!(
+            instructions[4]["operands"],
+            serde_json::json!([{"kind": "switch", "default": l2, "cases": [{"key": 5, "target": l2}]}])
+        );
+        assert_eq!(
+            instructions[6],
+            serde_json::json!({"offset": 53, "opcode": "ret", "wide": true, "operands": [{"kind": "local", "index": 301}]})
+        );
+    }
+
+    #[test]
+    fn test_utf8_policy() {
+        let class = assemble_one(
+            r#"
+.class public Foo
+.super java/lang/Object
+.const [30] = String [31]
+.const [31] = Utf8 b"bad\xFF"
+
+.method public static main : ([Ljava/lang/String;)V
+    .code stack 1 locals 1
+        ldc [30]
+        pop
+        return
+    .end code
+.end method
+.end class
+"#,
+        );
+
+        let out = decompile(&class, serde_json::json!({}));
+        assert_eq!(out["success"], false);
+        assert!(out["error"].as_str().unwrap().contains("Invalid Modified UTF-8"));
+
+        let out = decompile(&class, serde_json::json!({"utf8_policy": "replace"}));
+        assert!(out["output"].as_str().unwrap().contains(r#"ldc "bad\uFFFD""#));
+
+        let out = decompile(&class, serde_json::json!({"utf8_policy": "raw_escape", "roundtrip": true}));
+        let output = out["output"].as_str().unwrap();
+        assert!(output.contains(r#"Utf8 b"\x62\x61\x64\xFF""#));
+        assert_eq!(assemble_one(output), class);
+
+        let out = decompile(&class, serde_json::json!({"utf8_policy": "lenient"}));
+        assert_eq!(out["success"], false);
+    }
+
+    #[test]
+    fn test_benchmark_decompile() {
+        let class = assemble_one(HELLO);
+        let size = krakatau_lib::disassemble(&class, ParserOptions { no_short_code_attr: false }, Default::default())
+            .unwrap()
+            .1
+            .len() as i64;
+
+        assert_eq!(benchmark_decompile(class.as_ptr(), class.len(), 0), 0);
+        assert_eq!(benchmark_decompile(class.as_ptr(), class.len(), 1), size);
+        assert_eq!(benchmark_decompile(class.as_ptr(), class.len(), 7), size * 7);
+        assert_eq!(benchmark_decompile(class.as_ptr(), 4, 1), -2);
+        assert_eq!(benchmark_decompile(std::ptr::null(), 0, 1), -1);
+    }
+
+    #[test]
+    fn test_call_graph() {
+        let class = assemble_one(
+            r#"
+.class public Foo
+.super java/lang/Object
+
+.method public static a : ()V
+    .code stack 0 locals 0
+        invokestatic Method Foo b ()V
+        invokestatic Method Foo b ()V
+        invokestatic Method java/lang/System gc ()V
+        return
+    .end code
+.end method
+
+.method public static b : ()V
+    .code stack 0 locals 0
+        return
+    .end code
+.end method
+.end class
+"#,
+        );
+
+        let out = class_request(call_graph_json, &class, serde_json::json!({}));
+        assert_eq!(out["edges"], serde_json::json!([{"from": "a()V", "to": "b()V", "external": false}]));
+
+        let out = class_request(call_graph_json, &class, serde_json::json!({"include_external": true}));
+        assert_eq!(out["edges"].as_array().unwrap().len(), 2);
+        assert_eq!(out["edges"][1]["to"], "java/lang/System.gc()V");
+    }
+
+    #[test]
+    fn test_output_alignment() {
+        let assemble = |extra: serde_json::Value| {
+            let mut request = serde_json::json!({"file_path": "hello.j", "source_code": HELLO});
+            request.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            call(assemble_json, request)
+        };
+        let plain = assemble(serde_json::json!({}));
+        let plain_len = decode_base64(plain["class_files"][0]["base64_content"].as_str().unwrap()).unwrap().len();
+        assert_eq!(plain["class_files"][0]["padding"], 0);
+
+        for alignment in [4, 8, 64] {
+            let out = assemble(serde_json::json!({"output_alignment": alignment}));
+            let data = decode_base64(out["class_files"][0]["base64_content"].as_str().unwrap()).unwrap();
+            assert_eq!(data.len() % alignment, 0);
+            assert_eq!(data.len() - plain_len, out["class_files"][0]["padding"].as_u64().unwrap() as usize);
+            // The padding is an attribute rather than trailing data, so the class still parses
+            let raw = RawClass::parse(&data).unwrap();
+            assert!(raw.attrs.last().unwrap().data.iter().all(|&b| b == 0));
+            let output = decompile(&data, serde_json::json!({}))["output"].as_str().unwrap().to_owned();
+            assert!(output.contains("Padding"), "{}", output);
+        }
+
+        for alignment in [0, 3, 12, 8192, 1 << 30] {
+            assert_eq!(assemble(serde_json::json!({"output_alignment": alignment}))["success"], false);
+        }
+
+        // Already aligned classes aren't padded again
+        let out = assemble(serde_json::json!({"output_alignment": 64}));
+        let aligned = decode_base64(out["class_files"][0]["base64_content"].as_str().unwrap()).unwrap();
+        assert_eq!(pad_class(&aligned, 64).unwrap(), aligned);
+        assert_eq!(pad_class(&aligned, 8).unwrap(), aligned);
+
+        // The attribute count can't overflow
+        let class = decode_base64(plain["class_files"][0]["base64_content"].as_str().unwrap()).unwrap();
+        let raw = RawClass::parse(&class).unwrap();
+        let attrs = vec![raw_class::RawAttr { name: 1, data: &[] }; u16::MAX as usize];
+        let full = RawClass { attrs, ..raw }.to_bytes();
+        let alignment = 1 << (full.len().trailing_zeros() + 1);
+        assert_eq!(pad_class(&full, alignment).unwrap_err(), "Class has too many attributes to add padding");
+    }
+
+    #[test]
+    fn test_explain_assemble_error() {
+        let source = HELLO.replace(".end method\n", "");
+        let request = |explain: bool| {
+            call(assemble_json, serde_json::json!({"file_path": "hello.j", "source_code": source, "explain": explain}))
+        };
+
+        let out = request(true);
+        assert_eq!(out["success"], false);
+        assert_eq!(out["explanation"]["category"], "unterminated_block");
+        assert!(out["explanation"]["explanation"].as_str().unwrap().contains("unterminated .method block"));
+        assert!(out["explanation"]["suggestion"].as_str().unwrap().contains("`.end method`"));
+
+        assert_eq!(request(false)["explanation"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_minify_class() {
+        let class = assemble_one(
+            r#"
+.class public Foo
+.super java/lang/Object
+.sourcefile "Foo.java"
+
+.method public static main : ([Ljava/lang/String;)V
+    .runtime invisible annotations
+        .annotation LBuildInfo;
+        .end annotation
+    .end runtime
+    .code stack 2 locals 1
+L0:     getstatic Field java/lang/System out Ljava/io/PrintStream;
+L3:     ldc "Hello World!"
+L5:     invokevirtual Method java/io/PrintStream println (Ljava/lang/Object;)V
+L8:     return
+L9:
+        .linenumbertable
+            L0 3
+            L8 4
+        .end linenumbertable
+        .localvariabletable
+            0 is args [Ljava/lang/String; from L0 to L9
+        .end localvariabletable
+    .end code
+.end method
+.end class
+"#,
+        );
+
+        let out = class_request(minify_class_json, &class, serde_json::json!({}));
+        let minified = decode_base64(out["base64_content"].as_str().unwrap()).unwrap();
+        assert_eq!(out["original_size"], class.len());
+        assert_eq!(out["minified_size"], minified.len());
+        assert!(out["bytes_saved"].as_i64().unwrap() > 0);
+
+        let output = decompile(&minified, serde_json::json!({}))["output"].as_str().unwrap().to_string();
+        for removed in [".sourcefile", ".linenumbertable", ".localvariabletable", "BuildInfo"] {
+            assert!(!output.contains(removed));
+        }
+        let stripped = decompile(&class, serde_json::json!({"strip_debug": true}))["output"].as_str().unwrap().to_string();
+        let code = |s: &str| s.lines().filter(|l| l.trim_start().starts_with('L')).map(str::to_owned).collect::<Vec<_>>();
+        assert_eq!(code(&output), code(&stripped));
+
+        // Categories can be kept
+        let out = class_request(minify_class_json, &class, serde_json::json!({"strip_invisible_annotations": false}));
+        let kept = decode_base64(out["base64_content"].as_str().unwrap()).unwrap();
+        assert!(decompile(&kept, serde_json::json!({}))["output"].as_str().unwrap().contains("BuildInfo"));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_content_encoding() {
+        use std::io::Write;
+        let class = assemble_one(HELLO);
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&class).unwrap();
+        let compressed = gz.finish().unwrap();
+
+        let plain = decompile(&class, serde_json::json!({}));
+        let out = decompile(&compressed, serde_json::json!({"content_encoding": "gzip"}));
+        assert_eq!(out["output"], plain["output"]);
+
+        let out = decompile(&class, serde_json::json!({"content_encoding": "gzip"}));
+        assert_eq!(out["success"], false);
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    #[test]
+    fn test_gzip_requires_feature() {
+        let out = decompile(&assemble_one(HELLO), serde_json::json!({"content_encoding": "gzip"}));
+        assert_eq!(out["success"], false);
+        assert!(out["error"].as_str().unwrap().contains("gzip feature"));
+    }
+
+    #[test]
+    fn test_code_size() {
+        let source = format!(
+            ".class public Foo\n.super java/lang/Object\n\n\
+             .method public static small : ()V\n    .code stack 0 locals 0\n        return\n    .end code\n.end method\n\n\
+             .method public static big : ()V\n    .code stack 0 locals 0\n{}        return\n    .end code\n.end method\n.end class\n",
+            "        nop\n".repeat(61000)
+        );
+        let class = assemble_one(&source);
+
+        let out = class_request(code_size_json, &class, serde_json::json!({}));
+        assert_eq!(out["largest"], serde_json::json!({"method": "big()V", "code_length": 61001}));
+        assert_eq!(out["over_threshold"].as_array().unwrap().len(), 1);
+        assert_eq!(out["max_code_length"], 65535);
+
+        let out = class_request(code_size_json, &class, serde_json::json!({"warning_threshold": 0}));
+        assert_eq!(out["over_threshold"][1], serde_json::json!({"method": "small()V", "code_length": 1}));
+        let out = class_request(code_size_json, &assemble_one(HELLO), serde_json::json!({}));
+        assert_eq!(out["over_threshold"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_int_format_hex() {
+        let class = assemble_one(
+            r#"
+.class public Foo
+.super java/lang/Object
+
+.method public static masks : ()V
+    .code stack 2 locals 0
+        ldc 16711935
+        bipush -1
+        sipush 255
+        ldc2_w -9223372036854775808L
+        return
+    .end code
+.end method
+.end class
+"#,
+        );
+
+        let out = decompile(&class, serde_json::json!({"int_format": "hex"}));
+        let text = out["output"].as_str().unwrap();
+        assert!(text.contains("ldc 0xFF00FF\n"));
+        assert!(text.contains("bipush -0x1\n"));
+        assert!(text.contains("sipush 0xFF\n"));
+        assert!(text.contains("ldc2_w -0x8000000000000000L\n"));
+        assert!(!decompile(&class, serde_json::json!({}))["output"].as_str().unwrap().contains("0x"));
+
+        let roundtrip = decompile(&class, serde_json::json!({"int_format": "hex", "roundtrip": true}));
+        assert_eq!(assemble_one(roundtrip["output"].as_str().unwrap()), class);
+    }
+
+    #[test]
+    fn test_structural_hash() {
+        let with_lines = HELLO.replace("        getstatic", "    L0: getstatic").replace(
+            "    .end code",
+            "        .linenumbertable\n            L0 5\n        .end linenumbertable\n    .end code",
+        );
+        assert_ne!(with_lines, HELLO);
+        let hash = |source: &str| {
+            let out = class_request(structural_hash_json, &assemble_one(source), serde_json::json!({}));
+            out["hash"].as_str().unwrap().to_string()
+        };
+
+        assert_eq!(hash(HELLO).len(), 64);
+        assert_eq!(hash(&with_lines), hash(HELLO));
+        assert_ne!(hash(&HELLO.replace("Hello World!", "Goodbye")), hash(HELLO));
+    }
+
+    #[test]
+    fn test_api_usage() {
+        let class = assemble_one(HELLO);
+        let out = class_request(api_usage_json, &class, serde_json::json!({}));
+        assert_eq!(out["methods"], serde_json::json!(["java/io/PrintStream#println (Ljava/lang/Object;)V"]));
+
+        let out = class_request(api_usage_json, &class, serde_json::json!({"prefixes": ["javax/"]}));
+        assert_eq!(out["methods"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_report_unused_constants() {
+        let source = HELLO.replace(".super java/lang/Object \n", ".super java/lang/Object \n.const [1] = Utf8 unused\n");
+        let class = assemble_one(&source);
+        let out = decompile(&class, serde_json::json!({"report_unused_constants": true}));
+        assert_eq!(out["unused_constants"], serde_json::json!({"count": 1, "indices": [1]}));
+        assert!(!out["output"].as_str().unwrap().contains("unused"));
+
+        let out = decompile(&assemble_one(HELLO), serde_json::json!({"report_unused_constants": true}));
+        assert_eq!(out["unused_constants"]["count"], 0);
+        assert!(decompile(&class, serde_json::json!({}))["unused_constants"].is_null());
+
+        // A string which looks like a reference doesn't make anything used
+        let source = HELLO
+            .replace(".super java/lang/Object \n", ".super java/lang/Object \n.const [1] = Utf8 unused\n")
+            .replace("Hello World!", "see [1]");
+        let out = decompile(&assemble_one(&source), serde_json::json!({"report_unused_constants": true}));
+        assert_eq!(out["unused_constants"]["indices"], serde_json::json!([1]));
+
+        // References from attributes, such as a constant field value, count
+        let source = HELLO.replace(
+            ".super java/lang/Object \n",
+            ".super java/lang/Object \n.const [1] = Int 7\n.field static final X I = [1]\n",
+        );
+        let out = decompile(&assemble_one(&source), serde_json::json!({"report_unused_constants": true}));
+        assert_eq!(out["unused_constants"]["count"], 0);
+    }
+
+    #[test]
+    fn test_split_assembly() {
+        let first = ".version 52 0\n.class public Foo\n.super java/lang/Object\n.const [s] = String \".end class\"\n.end class";
+        let second = ".class public final Bar\n.super java/lang/Object ; .end class\n.end class";
+        let source = format!("; header comment\n{}\n\n{}\n", first, second);
+
+        let out = call(split_assembly_json, serde_json::json!({"source_code": source}));
+        assert_eq!(
+            out["classes"],
+            serde_json::json!([{"name": "Foo", "source": first}, {"name": "Bar", "source": second}])
+        );
+
+        let unterminated = format!("{}\n{}", first, HELLO.replace(".end class", ""));
+        let out = call(split_assembly_json, serde_json::json!({"source_code": unterminated}));
+        assert_eq!(out["success"], false);
+    }
+
+    #[test]
+    fn test_metadata_has_main() {
+        let has_main = |source: &str| {
+            let out = class_request(metadata_json, &assemble_one(source), serde_json::json!({}));
+            out["has_main"].clone()
+        };
+        assert_eq!(has_main(HELLO), true);
+        assert_eq!(has_main(&HELLO.replace("public static main", "public main")), false);
+        assert_eq!(has_main(&HELLO.replace("public static main", "static main")), false);
+        assert_eq!(has_main(&HELLO.replace("([Ljava/lang/String;)V", "([Ljava/lang/Object;)V")), false);
+    }
+
+    #[test]
+    fn test_get_response_chunk() {
+        let _guard = FFI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let class = assemble_one(HELLO);
+        let json = serde_json::json!({"file_path": "Foo.class", "base64_content": encode_base64(&class)}).to_string();
+        let len = decompile_json(json.as_ptr(), json.len());
+        assert!(len > 0);
+        let full = unsafe { std::slice::from_raw_parts(get_response_ptr(), len as usize) }.to_vec();
+
+        let mut chunks = Vec::new();
+        loop {
+            let n = get_response_chunk(chunks.len(), 100);
+            assert!((0..=100).contains(&n));
+            if n == 0 {
+                break;
+            }
+            chunks.extend_from_slice(unsafe { std::slice::from_raw_parts(get_response_chunk_ptr(), n as usize) });
+        }
+        assert_eq!(chunks, full);
+        assert_eq!(get_response_chunk(full.len() + 1, 100), -1);
+        free_response();
+    }
+
+    #[test]
+    fn test_get_response_chunk_large() {
+        let _guard = FFI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        // Enough long strings to take the output well past 512KB
+        let ldcs: String = (0..24).map(|i| format!("        ldc \"{}{}\"\n        pop\n", i, "x".repeat(30000))).collect();
+        let source = format!(
+            ".class public Big\n.super java/lang/Object\n.method public static f : ()V\n    .code stack 1 locals 0\n\
+             {}        return\n    .end code\n.end method\n.end class\n",
+            ldcs
+        );
+        let class = assemble_one(&source);
+        let json = serde_json::json!({"file_path": "Big.class", "base64_content": encode_base64(&class)}).to_string();
+        let len = decompile_json(json.as_ptr(), json.len());
+        assert!(len > 512 * 1024, "{}", len);
+        let full = unsafe { std::slice::from_raw_parts(get_response_ptr(), len as usize) }.to_vec();
+
+        let window = 64 * 1024;
+        let mut chunks = Vec::new();
+        loop {
+            let n = get_response_chunk(chunks.len(), window);
+            if n == 0 {
+                break;
+            }
+            chunks.extend_from_slice(unsafe { std::slice::from_raw_parts(get_response_chunk_ptr(), n as usize) });
+        }
+        assert_eq!(chunks, full);
+        let response: serde_json::Value = serde_json::from_slice(&chunks).unwrap();
+        assert_eq!(response["success"], true);
+        free_response();
+    }
+
+    #[test]
+    fn test_canonicalize_class() {
+        let plain = assemble_one(HELLO);
+        // Pin the string constant to the start of the pool
+        let pinned = ".super java/lang/Object \n.const [1] = Utf8 \"Hello World!\"\n.const [2] = String [1]\n";
+        let reordered = assemble_one(
+            &HELLO
+                .replace(".super java/lang/Object \n", pinned)
+                .replace("ldc \"Hello World!\"", "ldc [2]"),
+        );
+        assert_ne!(plain, reordered);
+        let mut padded = plain.clone();
+        padded.extend_from_slice(&[0; 8]);
+
+        let canonical = |class: &[u8]| {
+            let out = class_request(canonicalize_class_json, class, serde_json::json!({}));
+            decode_base64(out["base64_content"].as_str().unwrap()).unwrap()
+        };
+        assert_eq!(canonical(&reordered), canonical(&plain));
+        assert_eq!(canonical(&padded), canonical(&plain));
+        assert_eq!(canonical(&canonical(&plain)), canonical(&plain));
+    }
+
+    #[test]
+    fn test_set_source_file() {
+        let set = |class: &[u8], source_file: &str| {
+            let out = class_request(set_source_file_json, class, serde_json::json!({"source_file": source_file}));
+            decode_base64(out["base64_content"].as_str().unwrap()).unwrap()
+        };
+        let text = |class: &[u8]| decompile(class, serde_json::json!({}))["output"].as_str().unwrap().to_string();
+
+        let class = set(&assemble_one(HELLO), "Hello.java");
+        assert!(text(&class).contains(".sourcefile \"Hello.java\"\n"));
+
+        let class = set(&class, "Renamed.java");
+        let renamed = text(&class);
+        assert!(renamed.contains(".sourcefile \"Renamed.java\"\n"));
+        assert_eq!(renamed.matches(".sourcefile").count(), 1);
+
+        let class = set(&class, "");
+        assert!(!text(&class).contains(".sourcefile"));
+    }
+
+    #[test]
+    fn test_verify_frames() {
+        let source = |version: u16| {
+            format!(
+                r#"
+.version {} 0
+.class public Foo
+.super java/lang/Object
+
+.method public static branchy : (I)V
+    .code stack 1 locals 1
+        iload_0
+        ifeq L5
+    L5: return
+    .end code
+.end method
+
+.method public static straight : ()V
+    .code stack 0 locals 0
+        return
+    .end code
+.end method
+.end class
+"#,
+                version
+            )
+        };
+
+        let out = decompile(&assemble_one(&source(52)), serde_json::json!({"verify_frames": true}));
+        assert_eq!(out["missing_frames"], serde_json::json!(["branchy(I)V"]));
+        let out = decompile(&assemble_one(&source(49)), serde_json::json!({"verify_frames": true}));
+        assert_eq!(out["missing_frames"], serde_json::json!([]));
+
+        let with_frame = source(52).replace("    L5: return", "    L5:\n        .stack same\n        return");
+        let out = decompile(&assemble_one(&with_frame), serde_json::json!({"verify_frames": true}));
+        assert_eq!(out["missing_frames"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_annotation_comment_prefix() {
+        let class = assemble_one(HELLO);
+        let out = decompile(&class, serde_json::json!({"annotate_attributes": true, "annotation_comment_prefix": "//"}));
+        let text = out["output"].as_str().unwrap();
+        assert!(text.contains("    // Code, "));
+        assert!(!text.contains("; Code, "));
+        assert_eq!(out["roundtrip_lossy"], true);
+
+        let out = decompile(&class, serde_json::json!({"annotate_attributes": true}));
+        assert!(out["output"].as_str().unwrap().contains("    ; Code, "));
+    }
+
+    #[test]
+    fn test_jump_targets() {
+        let class = assemble_one(
+            r#"
+.class public Foo
+.super java/lang/Object
+
+.method public static f : (I)I
+    .code stack 1 locals 1
+    L0: iload_0
+    L1: ifeq L6
+    L4: iconst_1
+    L5: ireturn
+    L6: iconst_0
+    L7: ireturn
+    L8: astore_0
+    L9: iconst_m1
+    L10: ireturn
+        .catch java/lang/RuntimeException from L0 to L8 using L8
+    .end code
+.end method
+.end class
+"#,
+        );
+        let request = serde_json::json!({"method_name": "f", "method_descriptor": "(I)I"});
+        let out = class_request(jump_targets_json, &class, request);
+        assert_eq!(out["targets"], serde_json::json!([6, 8]));
+        assert_eq!(
+            out["edges"],
+            serde_json::json!([
+                {"from_offset": 1, "to_offset": 6, "kind": "conditional"},
+                {"from_offset": 0, "to_offset": 8, "kind": "exception_handler"},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_assemble_size_limits() {
+        let source = format!(
+            ".class public Foo\n.super java/lang/Object\n\n\
+             .method public static big : ()V\n    .code stack 0 locals 0\n{}        return\n    .end code\n.end method\n.end class\n",
+            "        nop\n".repeat(66000)
+        );
+        let assemble = |limits: serde_json::Value| {
+            let mut request = serde_json::json!({"file_path": "Foo.j", "source_code": source});
+            request.as_object_mut().unwrap().extend(limits.as_object().unwrap().clone());
+            call(assemble_json, request)
+        };
+
+        assert_eq!(assemble(serde_json::json!({}))["success"], true);
+        let out = assemble(serde_json::json!({"max_method_code": 65535}));
+        assert_eq!(out["success"], false);
+        assert_eq!(
+            out["error"],
+            "Method big()V of Foo has 66001 bytes of code, exceeding max_method_code of 65535"
+        );
+
+        let out = assemble(serde_json::json!({"max_members": 0}));
+        assert_eq!(out["error"], "Foo declares 1 methods, exceeding max_members of 0");
+        assert_eq!(assemble(serde_json::json!({"max_members": 1}))["success"], true);
+    }
+
+    #[test]
+    fn test_dual_output() {
+        let class = assemble_one(HELLO);
+        let out = decompile(&class, serde_json::json!({"dual_output": true}));
+        assert_eq!(out["success"], true);
+        let text = out["output"].as_str().unwrap();
+        let methods = out["output_ast"]["methods"].as_array().unwrap();
+        assert_eq!(methods.len(), text.lines().filter(|l| l.starts_with(".method ")).count());
+        assert_eq!(out["output_ast"]["name"], "Foo");
+
+        let main = &methods[0];
+        assert_eq!(main["name"], "main");
+        let opcodes: Vec<_> = main["instructions"].as_array().unwrap().iter().map(|i| &i["opcode"]).collect();
+        assert_eq!(opcodes, ["getstatic", "ldc", "invokevirtual", "return"]);
+        let operands = main["instructions"][1]["operands"].as_str().unwrap();
+        assert!(text.contains(&format!("ldc {}", operands)));
+
+        let out = decompile(&class, serde_json::json!({}));
+        assert!(out["output_ast"].is_null());
+    }
+
+    #[test]
+    fn test_parse_failure_keeps_file_path() {
+        let failed = |f: extern "C" fn(*const u8, usize) -> i32, json: &str| {
+            let _guard = FFI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            let code = f(json.as_ptr(), json.len());
+            assert!(code > 0);
+            read_response(code)
+        };
+
+        // Well formed, but the wrong shape
+        let out = failed(decompile_json, r#"{"file_path": "a/Foo.class", "base64_content": 5}"#);
+        assert_eq!(out["success"], false);
+        assert_eq!(out["file_path"], "a/Foo.class");
+        // Cut off part way through
+        let out = failed(assemble_json, r#"{"source_code": ".class Foo", "file_path" : "Foo.j", "output_alig"#);
+        assert_eq!(out["file_path"], "Foo.j");
+        let out = failed(metadata_json, r#"{"file_path": "Bar.class", "base64_content": ["#);
+        assert_eq!(out["file_path"], "Bar.class");
+
+        let out = failed(metadata_json, r#"{"file_path": 7}"#);
+        assert_eq!(out["file_path"], "unknown");
+    }
+
+    #[test]
+    fn test_synthesize_opcode_class() {
+        let synthesize = |opcode: &str| call(synthesize_opcode_class_json, serde_json::json!({"opcode": opcode}));
+        let out = synthesize("iadd");
+        assert_eq!(out["success"], true);
+        let class = decode_base64(out["base64_content"].as_str().unwrap()).unwrap();
+        let text = decompile(&class, serde_json::json!({}))["output"].as_str().unwrap().to_owned();
+        assert!(text.lines().any(|l| l.split_whitespace().any(|w| w == "iadd")));
+
+        for opcode in [
+            "dup2_x2", "lload_3", "castore", "i2s", "tableswitch", "lookupswitch", "jsr_w", "ret", "wide", "dreturn",
+            "athrow", "putfield", "invokeinterface", "invokedynamic", "multianewarray", "monitorexit",
+        ] {
+            let out = synthesize(opcode);
+            assert_eq!(out["success"], true, "{}", opcode);
+            let class = decode_base64(out["base64_content"].as_str().unwrap()).unwrap();
+            let text = decompile(&class, serde_json::json!({}))["output"].as_str().unwrap().to_owned();
+            assert!(text.lines().any(|l| l.split_whitespace().any(|w| w == opcode)), "{}", opcode);
+        }
+
+        let out = synthesize("iadd2");
+        assert_eq!(out["success"], false);
+        assert_eq!(out["error"], "Unrecognized opcode iadd2");
+    }
+
+    #[test]
+    fn test_warnings_as_errors() {
+        let source = ".version 52 0\n.class public Foo\n.super java/lang/Object\n\n\
+             .method public static loop : ()V\n    .code stack 0 locals 0\nL0:     goto L0\n\
+             .end code\n.end method\n.end class\n";
+        let assemble = |strict: bool| {
+            let request = serde_json::json!({"file_path": "Foo.j", "source_code": source, "warnings_as_errors": strict});
+            call(assemble_json, request)
+        };
+        let warning = "Method loop()V of Foo has jumps but no stack map frames, which version 52 classes require";
+
+        let out = assemble(false);
+        assert_eq!(out["success"], true);
+        assert_eq!(out["warnings"], serde_json::json!([warning]));
+
+        let out = assemble(true);
+        assert_eq!(out["success"], false);
+        assert_eq!(out["error"], warning);
+        assert!(out["class_files"].is_null());
+
+        let request = serde_json::json!({"file_path": "hello.j", "source_code": HELLO, "warnings_as_errors": true});
+        let out = call(assemble_json, request);
+        assert_eq!(out["success"], true);
+        assert!(out.get("warnings").is_none());
+    }
+
+    #[test]
+    fn test_decompile_content_bytes() {
+        let class = assemble_one(HELLO);
+        let expected = decompile(&class, serde_json::json!({}));
+        let request = serde_json::json!({"file_path": "Foo.class", "content_bytes": class});
+        let out = call(decompile_json, request);
+        assert_eq!(out["success"], true);
+        assert_eq!(out["output"], expected["output"]);
+
+        // Takes precedence over base64_content
+        let out = decompile(&[0xCA, 0xFE], serde_json::json!({"content_bytes": class}));
+        assert_eq!(out["output"], expected["output"]);
+
+        let out = call(decompile_json, serde_json::json!({"file_path": "Foo.class", "content_bytes": []}));
+        assert_eq!(out["success"], false);
+        assert_eq!(out["error"], "content_bytes must not be empty");
+    }
+
+    #[test]
+    fn test_jar_max_total_uncompressed() {
+        let class = assemble_one(HELLO);
+        let zeros = vec![0u8; 1 << 20];
+        let jar = make_jar(&[("Foo.class", &class), ("Bomb.class", &zeros)]);
+        assert!(jar.len() < 10000);
+
+        let out = class_request(decompile_jar_json, &jar, serde_json::json!({"max_total_uncompressed": 100000}));
+        assert_eq!(out["success"], false);
+        assert_eq!(out["error"], "uncompressed size limit exceeded");
+
+        let jar = make_jar(&[("Foo.class", &class)]);
+        let limit = class.len();
+        let out = class_request(decompile_jar_json, &jar, serde_json::json!({"max_total_uncompressed": limit}));
+        assert_eq!(out["success"], true);
+        let out = class_request(decompile_jar_json, &jar, serde_json::json!({"max_total_uncompressed": limit - 1}));
+        assert_eq!(out["success"], false);
+    }
+
+    #[test]
+    fn test_metadata_local_variables() {
+        let class = assemble_one(
+            r#"
+.class public Foo
+.super java/lang/Object
+
+.method public static first : (Ljava/util/List;)Ljava/lang/Object;
+    .code stack 2 locals 1
+L0:     aload_0
+L1:     iconst_0
+L2:     invokeinterface InterfaceMethod java/util/List get (I)Ljava/lang/Object; 2
+L7:     areturn
+L8:
+        .localvariabletable
+            0 is list Ljava/util/List; from L0 to L8
+        .end localvariabletable
+        .localvariabletypetable
+            0 is list Ljava/util/List<Ljava/lang/String;>; from L0 to L8
+        .end localvariabletypetable
+    .end code
+.end method
+
+.method public static nodebug : ()V
+    .code stack 0 locals 0
+        return
+    .end code
+.end method
+.end class
+"#,
+        );
+
+        let out = class_request(metadata_json, &class, serde_json::json!({"include_local_variables": true}));
+        assert_eq!(
+            out["methods"][0]["local_variables"],
+            serde_json::json!([{
+                "index": 0,
+                "name": "list",
+                "descriptor": "Ljava/util/List;",
+                "signature": "Ljava/util/List<Ljava/lang/String;>;",
+                "start_pc": 0,
+                "length": 8,
+            }])
+        );
+        assert_eq!(out["methods"][1]["local_variables"], serde_json::json!([]));
+
+        let out = class_request(metadata_json, &class, serde_json::json!({}));
+        assert!(out["methods"][0].get("local_variables").is_none());
+    }
+
+    #[test]
+    fn test_relocate_package() {
+        let class = assemble_one(
+            r#"
+.class public org/old/Foo
+.super java/lang/Object
+.field dep Lorg/old/dep/Dep;
+.field deps [[Lorg/old/Dep;
+.field other Lorg/older/Dep;
+.field list Ljava/util/List; .fieldattributes
+    .signature "Ljava/util/List<Lorg/old/Dep;>;"
+.end fieldattributes
+
+.method public static make : (ILorg/old/Dep;)[Lorg/old/Dep;
+    .signature "<L:Lorg/old/Dep;T::Ljava/lang/Comparable<TT;>;>(ITL;)[Lorg/old/Dep;"
+    .code stack 1 locals 2
+        ldc "org/old/Dep"
+        pop
+        ldc Class [Lorg/old/Dep;
+        checkcast [Lorg/old/Dep;
+        areturn
+    .end code
+.end method
+.end class
+"#,
+        );
+
+        let request = serde_json::json!({"from_prefix": "org/old", "to_prefix": "com/shaded/"});
+        let out = class_request(relocate_package_json, &class, request);
+        assert_eq!(out["success"], true);
+        let relocated = decode_base64(out["base64_content"].as_str().unwrap()).unwrap();
+        let text = decompile(&relocated, serde_json::json!({}))["output"].as_str().unwrap().to_owned();
+
+        assert!(text.contains(".class public com/shaded/Foo"));
+        assert!(text.contains(".field dep Lcom/shaded/dep/Dep;"));
+        assert!(text.contains(".field deps [[Lcom/shaded/Dep;"));
+        assert!(text.contains(".field other Lorg/older/Dep;"));
+        assert!(text.contains("Ljava/util/List<Lcom/shaded/Dep;>;"));
+        assert!(text.contains(".method public static make : (ILcom/shaded/Dep;)[Lcom/shaded/Dep;"));
+        assert!(text.contains("<L:Lcom/shaded/Dep;T::Ljava/lang/Comparable<TT;>;>(ITL;)[Lcom/shaded/Dep;"));
+        assert!(text.contains("checkcast [Lcom/shaded/Dep;"));
+        assert!(text.contains("ldc \"org/old/Dep\""));
+        assert!(!text.contains("Lorg/old/"));
+    }
+
+    #[test]
+    fn test_supported_attributes() {
+        let out = {
+            let _guard = FFI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            read_response(supported_attributes())
+        };
+        let fully_supported = out["fully_supported"].as_array().unwrap();
+        assert!(fully_supported.contains(&serde_json::json!("Code")));
+        assert!(fully_supported.contains(&serde_json::json!("StackMapTable")));
+        assert!(out["opaque"].as_array().unwrap().contains(&serde_json::json!("SourceID")));
+
+        let class = assemble_one(&HELLO.replace(".end class", ".attribute \"SourceID\" b\"\\x00\\x01\"\n.end class"));
+        let text = decompile(&class, serde_json::json!({}))["output"].as_str().unwrap().to_owned();
+        assert!(text.contains(".attribute SourceID b'"));
+    }
+
+    #[test]
+    fn test_assemble_line_number_map() {
+        let source = r#"
+.class public Foo
+.super java/lang/Object
+
+.method public static twice : (I)I
+    .code stack 2 locals 1
+L0:     iload_0
+L1:     iconst_2
+L2:     imul
+L3:     ireturn
+    .end code
+.end method
+.end class
+"#;
+        let assemble = |methods: serde_json::Value| {
+            call(assemble_json, serde_json::json!({"file_path": "Foo.j", "source_code": source, "methods": methods}))
+        };
+
+        let methods = serde_json::json!([{
+            "method_name": "twice",
+            "method_descriptor": "(I)I",
+            "line_number_map": [{"label": "L0", "line": 7}, {"label": "L3", "line": 8}],
+        }]);
+        let out = assemble(methods);
+        assert_eq!(out["success"], true);
+        let class = decode_base64(out["class_files"][0]["base64_content"].as_str().unwrap()).unwrap();
+        let text = decompile(&class, serde_json::json!({}))["output"].as_str().unwrap().to_owned();
+        let table: Vec<_> = text
+            .lines()
+            .skip_while(|l| l.trim() != ".linenumbertable")
+            .skip(1)
+            .take_while(|l| l.trim() != ".end linenumbertable")
+            .map(str::trim)
+            .collect();
+        assert_eq!(table, ["L0 7", "L3 8"]);
+
+        let out = assemble(serde_json::json!([{"method_name": "thrice", "line_number_map": []}]));
+        assert_eq!(out["error"], "No method thrice with code in source");
+        let out = assemble(serde_json::json!([{"method_name": "twice", "line_number_map": [{"label": "L9", "line": 1}]}]));
+        assert_eq!(out["success"], false);
+
+        // Labels can't smuggle in other source
+        for label in ["L0 7\n        .end linenumbertable\n        nop", "L0 1", "0", "L", ""] {
+            let map = serde_json::json!([{"label": label, "line": 1}]);
+            let out = assemble(serde_json::json!([{"method_name": "twice", "line_number_map": map}]));
+            assert_eq!(out["error"], format!("Invalid label {:?} in line_number_map of method twice", label));
+        }
+
+        // The end of the code is found even with a comment after it
+        let commented = source.replace("    .end code", "    .end code ; of twice");
+        let out = call(
+            assemble_json,
+            serde_json::json!({"file_path": "Foo.j", "source_code": commented, "methods": [{
+                "method_name": "twice",
+                "line_number_map": [{"label": "L0", "line": 7}],
+            }]}),
+        );
+        assert_eq!(out["success"], true);
+
+        // Quoted names are matched by their value
+        let quoted = source.replace(".method public static twice", r#".method public static "two words""#);
+        let out = call(
+            assemble_json,
+            serde_json::json!({"file_path": "Foo.j", "source_code": quoted, "methods": [{
+                "method_name": "two words",
+                "line_number_map": [{"label": "L0", "line": 7}],
+            }]}),
+        );
+        assert_eq!(out["success"], true);
+
+        // Errors after the inserted table are reported against the source as written
+        let broken = source.replace(
+            ".end class",
+            ".method static broken : ()V\n    .code stack 0 locals 0\n        bogus\n    .end code\n.end method\n.end class",
+        );
+        let assemble_broken = |methods: serde_json::Value| {
+            call(assemble_json, serde_json::json!({"file_path": "Foo.j", "source_code": broken, "methods": methods}))
+        };
+        let plain = assemble_broken(serde_json::json!([]));
+        assert_eq!(plain["success"], false);
+        let mapped =
+            assemble_broken(serde_json::json!([{"method_name": "twice", "line_number_map": [{"label": "L0", "line": 7}]}]));
+        assert_eq!(mapped, plain);
+
+        let insertions = [(10, 5), (20, 3)];
+        let offsets: Vec<_> =
+            [0, 9, 10, 14, 15, 24, 25, 27, 28, 40].iter().map(|&pos| original_offset(&insertions, pos)).collect();
+        assert_eq!(offsets, [0, 9, 10, 10, 10, 19, 20, 20, 20, 32]);
+    }
+
+    #[test]
+    fn test_has_response() {
+        let _guard = FFI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        free_response();
+        assert_eq!(has_response(), 0);
+        assert!(get_response_ptr().is_null());
+        assert_eq!(get_response_length(), 0);
+
+        let len = version_info_json();
+        assert!(len > 0);
+        assert_eq!(has_response(), 1);
+        assert!(!get_response_ptr().is_null());
+        assert_eq!(get_response_length(), len);
+
+        free_response();
+        assert_eq!(has_response(), 0);
+        assert!(get_response_ptr().is_null());
+        assert_eq!(get_response_length(), 0);
+
+        // A response too large to store leaves nothing behind, rather than the previous response
+        version_info_json();
+        assert_eq!(store_json_response("x".repeat(MAX_RESPONSE_LEN)), -1);
+        assert_eq!(has_response(), 0);
+        assert!(get_response_ptr().is_null());
+
+        // So does a call without any input
+        let calls: [extern "C" fn(*const u8, usize) -> i32; 3] = [decompile_json, assemble_json, constant_pool_json];
+        for f in calls {
+            version_info_json();
+            assert_eq!(f(std::ptr::null(), 0), -1);
+            assert_eq!(has_response(), 0);
+        }
+    }
+
+    #[test]
+    fn test_assemble_jar_manifest() {
+        use std::io::Read;
+        let jar = |manifest: serde_json::Value| {
+            call(assemble_jar_json, serde_json::json!({"source_code": HELLO, "manifest": manifest}))
+        };
+
+        let out = jar(serde_json::json!({"Main-Class": "Foo", "Created-By": "test"}));
+        assert_eq!(out["success"], true);
+        assert_eq!(out["entries"], serde_json::json!(["META-INF/MANIFEST.MF", "Foo.class"]));
+        let data = decode_base64(out["base64_content"].as_str().unwrap()).unwrap();
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(data)).unwrap();
+        let mut manifest = String::new();
+        zip.by_name("META-INF/MANIFEST.MF").unwrap().read_to_string(&mut manifest).unwrap();
+        assert_eq!(manifest, "Manifest-Version: 1.0\r\nCreated-By: test\r\nMain-Class: Foo\r\n\r\n");
+        assert!(zip.by_name("Foo.class").is_ok());
+
+        // Without a manifest, only the version is written
+        let out = jar(serde_json::Value::Null);
+        let data = decode_base64(out["base64_content"].as_str().unwrap()).unwrap();
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(data)).unwrap();
+        let mut manifest = String::new();
+        zip.by_name("META-INF/MANIFEST.MF").unwrap().read_to_string(&mut manifest).unwrap();
+        assert_eq!(manifest, "Manifest-Version: 1.0\r\n\r\n");
+
+        for bad in ["Main Class", "-Main", "", "Main:Class"] {
+            let out = jar(serde_json::json!({ (bad): "Foo" }));
+            assert_eq!(out["success"], false, "{}", bad);
+            assert!(out["error"].as_str().unwrap().contains("Invalid manifest attribute name"));
+        }
+        let out = jar(serde_json::json!({"Main-Class": "Foo\nEvil: yes"}));
+        assert_eq!(out["success"], false);
+    }
+
+    #[test]
+    fn test_manifest_long_lines() {
+        let mut attrs = BTreeMap::new();
+        attrs.insert("Class-Path".to_string(), "lib/a.jar ".repeat(20));
+        let manifest = String::from_utf8(jar::manifest(&attrs).unwrap()).unwrap();
+        let lines: Vec<_> = manifest.split("\r\n").collect();
+        assert!(lines.iter().all(|l| l.len() <= 72));
+        assert!(lines[2].starts_with(' '));
+        let joined = manifest.replace("\r\n ", "");
+        assert!(joined.contains(&format!("Class-Path: {}\r\n", "lib/a.jar ".repeat(20))));
+    }
+
+    #[test]
+    fn test_roundtrip_diff() {
+        // The assembler would put these constants elsewhere, unless told to keep the pool order
+        let source = HELLO.replace(
+            ".super java/lang/Object \n",
+            ".super java/lang/Object \n.const [40] = String \"Hello World!\"\n.const [2] = Utf8 unused\n",
+        );
+        let class = assemble_one(&source);
+
+        let out = class_request(roundtrip_diff_json, &class, serde_json::json!({}));
+        assert_eq!(out["success"], true);
+        assert_eq!(out["identical"], false);
+        assert_eq!(out["original_size"], class.len());
+        let changed = out["changed_sections"].as_array().unwrap();
+        assert!(changed.contains(&serde_json::json!("constant_pool")));
+        assert!(!changed.contains(&serde_json::json!("magic")));
+        let ranges = out["ranges"].as_array().unwrap();
+        assert!(!ranges.is_empty());
+        // Nothing changes before the pool, which starts at offset 8
+        assert!(ranges[0]["offset"].as_u64().unwrap() >= 8);
+        let shown = |r: &serde_json::Value, key: &str| r[key].as_str().unwrap().len();
+        assert!(ranges.iter().all(|r| shown(r, "original_bytes") <= 128 && shown(r, "new_bytes") <= 128));
+
+        let out = class_request(roundtrip_diff_json, &class, serde_json::json!({"max_ranges": 1}));
+        assert_eq!(out["ranges"].as_array().unwrap().len(), 1);
+        assert_eq!(out["truncated"], ranges.len() > 1);
+
+        let out = class_request(roundtrip_diff_json, &class, serde_json::json!({"roundtrip": true}));
+        assert_eq!(out["identical"], true);
+        assert_eq!(out["changed_sections"], serde_json::json!([]));
+        assert_eq!(out["ranges"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_wrap_columns() {
+        let class = assemble_one(LAMBDA);
+        let plain = decompile(&class, serde_json::json!({}));
+        let plain = plain["output"].as_str().unwrap();
+        assert!(plain.lines().any(|l| l.len() > 200));
+
+        let out = decompile(&class, serde_json::json!({"wrap_columns": 80}));
+        assert_eq!(out["roundtrip_lossy"], true);
+        assert!(out["reasons"].as_array().unwrap().contains(&serde_json::json!("long lines are wrapped")));
+        let wrapped = out["output"].as_str().unwrap();
+        assert!(wrapped.lines().count() > plain.lines().count());
+        // Only the tokens which are longer than the limit by themselves are left on overlong lines
+        for line in wrapped.lines().filter(|l| l.len() > 80) {
+            assert_eq!(line.split_whitespace().count(), 1, "{}", line);
+        }
+        // Continuation lines are indented past the instruction they belong to
+        assert!(!plain.contains("\n            ") && wrapped.contains("\n            "));
+        assert_eq!(
+            wrapped.split_whitespace().collect::<Vec<_>>(),
+            plain.split_whitespace().collect::<Vec<_>>()
+        );
+
+        let wrapped = wrap_lines("    ldc \"a b c d e f\" ; x y z\n", 10);
+        assert_eq!(wrapped, "    ldc\n        \"a b c d e f\"\n        ; x y z\n");
+
+        // Lines which are entirely a comment, like the disassembler's warning banners, are left alone
+        for line in ["; Warning! this comment is longer than the limit\n", "    ; so is this indented one\n"] {
+            assert_eq!(wrap_lines(line, 10), line);
+        }
+    }
+
+    #[test]
+    fn test_include_permitted_subclasses() {
+        let sealed = |header: &str| {
+            assemble_one(&format!(
+                ".version 61 0\n{}\n.super java/lang/Object\n.permittedsubclasses Circle Square\n.end class\n",
+                header
+            ))
+        };
+        for header in [".class public abstract Shape", ".class public interface abstract Shape"] {
+            let class = sealed(header);
+            let out = class_request(metadata_json, &class, serde_json::json!({"include_permitted_subclasses": true}));
+            assert_eq!(out["permitted_subclasses"], serde_json::json!(["Circle", "Square"]), "{}", header);
+
+            let out = class_request(metadata_json, &class, serde_json::json!({}));
+            assert!(out.get("permitted_subclasses").is_none());
+        }
+
+        let class = assemble_one(HELLO);
+        let out = class_request(metadata_json, &class, serde_json::json!({"include_permitted_subclasses": true}));
+        assert_eq!(out["permitted_subclasses"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_default_name() {
+        let class = assemble_one(HELLO);
+        let out = decompile(&class, serde_json::json!({"default_name": "Fallback"}));
+        assert_eq!(out["class_name"], "Foo");
+        assert_eq!(out["name_resolution_failed"], false);
+
+        // Point this_class at the invalid constant 0
+        let raw = RawClass::parse(&class).unwrap();
+        let broken = RawClass { this: 0, ..raw }.to_bytes();
+        let out = decompile(&broken, serde_json::json!({"default_name": "Fallback"}));
+        assert_eq!(out["success"], true);
+        assert_eq!(out["class_name"], "Fallback");
+        assert_eq!(out["name_resolution_failed"], true);
+
+        let out = decompile(&broken, serde_json::json!({}));
+        assert_eq!(out["class_name"], serde_json::Value::Null);
+        assert_eq!(out["name_resolution_failed"], true);
+    }
+
+    #[test]
+    fn test_include_nest_info() {
+        let class = |name: &str, body: &str| {
+            let header = format!(".version 55 0\n.class public {}\n.super java/lang/Object\n", name);
+            assemble_one(&format!("{}{}\n.end class\n", header, body))
+        };
+        let opts = serde_json::json!({"include_nest_info": true});
+
+        let out = class_request(metadata_json, &class("Outer", ".nestmembers Outer$Inner Outer$Other"), opts.clone());
+        assert_eq!(out["nest_info"]["nest_host"], serde_json::Value::Null);
+        assert_eq!(out["nest_info"]["nest_members"], serde_json::json!(["Outer$Inner", "Outer$Other"]));
+
+        let out = class_request(metadata_json, &class("Outer$Inner", ".nesthost Outer"), opts.clone());
+        assert_eq!(out["nest_info"]["nest_host"], "Outer");
+        assert_eq!(out["nest_info"]["nest_members"], serde_json::json!([]));
+
+        // Entries which aren't classes are kept in place, like unresolved permitted subclasses
+        let body = ".const [40] = Utf8 junk\n.nestmembers Outer$Inner [40]";
+        let out = class_request(metadata_json, &class("Outer", body), opts.clone());
+        assert_eq!(out["nest_info"]["nest_members"], serde_json::json!(["Outer$Inner", null]));
+
+        let out = class_request(metadata_json, &class("Outer$Inner", ".nesthost Outer"), serde_json::json!({}));
+        assert!(out.get("nest_info").is_none());
+    }
+
+    #[test]
+    fn test_assemble_size_estimate() {
+        let mut big = String::from(".version 52 0\n.class public Big\n.super java/lang/Object\n.field private count I\n");
+        for i in 0..50 {
+            big.push_str(&format!(
+                ".method public m{} : (I)I\n    .code stack 4 locals 2\n        iload_1\n        ldc {}\n        iadd\n\
+                 L4:     ifeq L10\n        aload_0\n        getfield Field Big count I\n        ireturn\n\
+                 .stack same\n\
+                 L10:    ldc \"value {}\"\n        invokevirtual Method java/lang/String length ()I\n        ireturn\n\
+                 .end code\n.end method\n",
+                i,
+                100000 + i,
+                i
+            ));
+        }
+        big.push_str(".end class\n");
+
+        for source in [HELLO, LAMBDA, &big] {
+            let out = call(assemble_size_estimate_json, serde_json::json!({"source_code": source}));
+            assert_eq!(out["success"], true);
+            let estimate = out["estimated_size"].as_u64().unwrap() as usize;
+            let actual = assemble_one(source).len();
+            assert!(actual / 2 <= estimate && estimate <= actual * 2, "estimated {} for {} bytes", estimate, actual);
+        }
+
+        let out = call(assemble_size_estimate_json, serde_json::json!({"source_code": big}));
+        assert_eq!(out["classes"], 1);
+        assert_eq!(out["fields"], 1);
+        assert_eq!(out["methods"], 50);
+        assert_eq!(out["instructions"], 50 * 10);
+
+        let out = call(assemble_size_estimate_json, serde_json::json!({"source_code": "; nothing here\n"}));
+        assert_eq!(out["success"], false);
+    }
+
+    #[test]
+    fn test_humanize_method_handles() {
+        let class = assemble_one(
+            ".version 52 0\n.class public Foo\n.super java/lang/Object\n\
+             .method public static handles : ()V\n    .code stack 1 locals 0\n\
+                     ldc MethodHandle invokeVirtual Method java/lang/String length ()I\n        pop\n\
+                     ldc MethodType (I)V\n        pop\n        ldc \"plain\"\n        pop\n        return\n\
+                 .end code\n.end method\n.end class\n",
+        );
+        let out = decompile(&class, serde_json::json!({"humanize_method_handles": true}));
+        let output = out["output"].as_str().unwrap();
+        let ldcs: Vec<_> = output.lines().filter(|l| l.trim_start().starts_with("ldc")).collect();
+        assert!(ldcs[0].ends_with(" ; REF_invokeVirtual java/lang/String.length()I"), "{}", ldcs[0]);
+        assert!(ldcs[1].ends_with(" ; MethodType (I)V"), "{}", ldcs[1]);
+        assert!(!ldcs[2].contains(';'));
+        // The comments don't stop the output from reassembling
+        assemble_one(output);
+
+        let out = decompile(&class, serde_json::json!({"humanize_method_handles": true, "annotation_comment_prefix": "#"}));
+        assert!(out["output"].as_str().unwrap().contains(" # REF_invokeVirtual java/lang/String.length()I"));
+
+        let out = decompile(&class, serde_json::json!({}));
+        assert!(!out["output"].as_str().unwrap().contains("REF_"));
+
+        // Names are escaped like any other string in the output, so they can't start a new line
+        let source = ".version 52 0\n.class public Foo\n.super java/lang/Object\n\
+                      .method public static handles : ()V\n    .code stack 1 locals 0\n\
+                          ldc MethodHandle invokeStatic Method Foo \"a\\nb\" ()V\n        pop\n        return\n\
+                      .end code\n.end method\n.end class\n";
+        let out = decompile(&assemble_one(source), serde_json::json!({"humanize_method_handles": true}));
+        let output = out["output"].as_str().unwrap();
+        let ldc = output.lines().find(|l| l.trim_start().starts_with("ldc")).unwrap();
+        assert!(ldc.ends_with(" ; REF_invokeStatic Foo.\"a\\u000Ab\"()V"), "{}", ldc);
+        assemble_one(output);
+    }
+
+    #[test]
+    fn test_target_profile() {
+        let assemble = |source: &str, profile: &str| {
+            call(assemble_json, serde_json::json!({"file_path": "Foo.j", "source_code": source, "target_profile": profile}))
+        };
+
+        let out = assemble(LAMBDA, "jvm5");
+        assert_eq!(out["success"], false);
+        let error = out["error"].as_str().unwrap();
+        assert!(error.contains("Foo has class version 52, but jvm5 only supports up to 49"), "{}", error);
+        assert!(error.contains("Foo uses method handle or method type constants, which jvm5 doesn't support"));
+        assert!(error.contains("Method main([Ljava/lang/String;)V of Foo uses invokedynamic, which jvm5 doesn't support"));
+
+        for profile in ["jvm8", "jvm17", "android"] {
+            assert_eq!(assemble(LAMBDA, profile)["success"], true, "{}", profile);
+        }
+        // The version is checked even when the class doesn't use any newer features
+        assert_eq!(assemble(HELLO, "jvm5")["success"], true);
+        assert_eq!(assemble(&HELLO.replace(".class", ".version 50 0\n.class"), "jvm5")["success"], false);
+
+        let out = assemble(HELLO, "jvm");
+        assert!(out["error"].as_str().unwrap().contains("Unknown target profile"));
+    }
+
+    #[test]
+    fn test_assemble_streaming() {
+        use std::rc::Rc;
+        let _guard = FFI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let source = format!("{}\n{}", HELLO, LAMBDA.replace("Foo", "Bar"));
+        let stream = |source: &str| assemble_streaming(source.as_ptr(), source.len());
+
+        let written = Rc::new(RefCell::new(Vec::new()));
+        let sink = written.clone();
+        set_chunk_writer(Some(Box::new(move |chunk: &[u8]| {
+            sink.borrow_mut().push(chunk.to_vec());
+            true
+        })));
+        assert_eq!(stream(&source), 2);
+
+        let expected = krakatau_lib::assemble(&source, AssemblerOptions {}).unwrap();
+        let records = written.borrow();
+        assert_eq!(records.len(), expected.len());
+        for (record, (_, class)) in records.iter().zip(&expected) {
+            let (len, data) = record.split_at(4);
+            assert_eq!(u32::from_le_bytes(len.try_into().unwrap()) as usize, class.len());
+            assert_eq!(data, &class[..]);
+        }
+        drop(records);
+
+        // Classes before an error are still written
+        written.borrow_mut().clear();
+        assert_eq!(stream(&format!("{}\n.class Broken\n.super\n.end class\n", HELLO)), -2);
+        assert_eq!(written.borrow().len(), 1);
+        assert!(read_response(0)["error"].as_str().unwrap().starts_with("Assembly error"));
+
+        set_chunk_writer(Some(Box::new(|_: &[u8]| false)));
+        assert_eq!(stream(&source), -3);
+        set_chunk_writer(None);
+        assert_eq!(stream(&source), -3);
+        assert_eq!(assemble_streaming(std::ptr::null(), 0), -1);
+    }
+
+    #[test]
+    fn test_missing_super_class() {
+        let class = assemble_one(HELLO);
+        let out = decompile(&class, serde_json::json!({}));
+        assert_eq!(out["warnings"], serde_json::json!([]));
+
+        let raw = RawClass::parse(&class).unwrap();
+        let broken = RawClass { super_: 0, ..raw }.to_bytes();
+        let warning = "Foo has no super class, which only java/lang/Object may omit";
+        let out = decompile(&broken, serde_json::json!({}));
+        assert_eq!(out["success"], true);
+        assert_eq!(out["warnings"], serde_json::json!([warning]));
+
+        let out = decompile(&broken, serde_json::json!({"warnings_as_errors": true}));
+        assert_eq!(out["success"], false);
+        assert_eq!(out["error"], warning);
+
+        // ACC_MODULE
+        let module = RawClass { access: 0x8000, super_: 0, ..RawClass::parse(&class).unwrap() }.to_bytes();
+        let out = decompile(&module, serde_json::json!({"warnings_as_errors": true}));
+        assert_eq!(out["success"], true);
+        assert_eq!(out["warnings"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_abi_json() {
+        let _guard = FFI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let out = read_response(abi_json());
+        let listed: Vec<&str> = out.as_array().unwrap().iter().map(|f| f["name"].as_str().unwrap()).collect();
+
+        // Compare against the signatures as written, ignoring formatting
+        let source: String = include_str!("lib.rs").split_whitespace().collect();
+        let marker = "#[no_mangle]pubextern\"C\"fn";
+        let exported: Vec<&str> = source.split(marker).skip(1).map(|s| &s[..s.find('{').unwrap()]).collect();
+        let names: Vec<&str> = exported.iter().map(|sig| &sig[..sig.find('(').unwrap()]).collect();
+        for name in &listed {
+            assert!(names.contains(name), "{} is not exported", name);
+        }
+        for name in &names {
+            assert!(listed.contains(name), "{} is missing from abi_json", name);
+        }
+
+        for f in out.as_array().unwrap() {
+            let params: Vec<String> = f["params"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|p| format!("{}:{}", p["name"].as_str().unwrap(), p["type"].as_str().unwrap()))
+                .collect();
+            let returns = match f["returns"].as_str().unwrap() {
+                "()" => String::new(),
+                ty => format!("->{}", ty),
+            };
+            let expected = format!("{}({}){}", f["name"].as_str().unwrap(), params.join(","), returns).replace(' ', "");
+            let sig = exported.iter().find(|sig| sig.starts_with(&format!("{}(", f["name"].as_str().unwrap()))).unwrap();
+            assert_eq!(sig.replace(",)", ")"), expected);
+        }
+    }
+
+    #[test]
+    fn test_group_interface_methods() {
+        let class = assemble_one(
+            ".version 52 0\n.class public interface abstract Shape\n.super java/lang/Object\n\
+             .method public static origin : ()V\n    .code stack 0 locals 0\n        return\n    .end code\n.end method\n\
+             .method public abstract area : ()D\n.end method\n\
+             .method public describe : ()V\n    .code stack 0 locals 1\n        return\n    .end code\n.end method\n\
+             .method public abstract name : ()Ljava/lang/String;\n.end method\n.end class\n",
+        );
+        let methods = |output: &str| -> Vec<String> {
+            let lines = output.lines().filter(|l| l.starts_with(".method") || l.starts_with("; "));
+            lines.map(|l| l.split(" : ").next().unwrap().rsplit(' ').next().unwrap().to_owned()).collect()
+        };
+
+        let out = decompile(&class, serde_json::json!({"group_interface_methods": true}));
+        let output = out["output"].as_str().unwrap();
+        assert!(output.contains("\n; Abstract methods\n"), "{}", output);
+        assert!(output.contains("\n; Default and private methods\n"));
+        assert!(output.contains("\n; Static methods\n"));
+        assert_eq!(methods(output), ["methods", "area", "name", "methods", "describe", "methods", "origin"]);
+        assert_eq!(out["roundtrip_lossy"], true);
+
+        // Classfile order is kept otherwise
+        let out = decompile(&class, serde_json::json!({"group_interface_methods": true, "roundtrip": true}));
+        assert_eq!(methods(out["output"].as_str().unwrap()), ["origin", "area", "describe", "name"]);
+
+        // Nothing is reordered in a class which isn't an interface
+        let out = decompile(&assemble_one(HELLO), serde_json::json!({"group_interface_methods": true}));
+        assert_eq!(out["roundtrip_lossy"], false);
+
+        // Archives report it per class
+        let jar = make_jar(&[("Shape.class", &class), ("Foo.class", &assemble_one(HELLO))]);
+        let options = serde_json::json!({"group_interface_methods": true, "roundtrip": true});
+        let out = class_request(decompile_jar_json, &jar, options);
+        assert_eq!(out["classes"][0]["roundtrip_lossy"], false);
+        assert_eq!(out["classes"][0]["reasons"], serde_json::json!([]));
+        let options = serde_json::json!({"group_interface_methods": true});
+        let out = class_request(decompile_jar_json, &jar, options);
+        assert!(out["classes"][0]["reasons"].as_array().unwrap().contains(&"interface methods are reordered".into()));
+        assert!(!out["classes"][1]["reasons"].as_array().unwrap().contains(&"interface methods are reordered".into()));
+    }
+
+    #[test]
+    fn test_duplicate_members() {
+        let method = |desc: &str| {
+            let code = ".code stack 0 locals 1\n        return\n    .end code";
+            format!(".method public static run : {}\n    {}\n.end method\n", desc, code)
+        };
+        let source = format!(
+            ".class public Foo\n.super java/lang/Object\n\
+             .field x I\n.field x J\n.field y I\n.field y I\n{}{}{}{}.end class\n",
+            method("()V"),
+            method("()V"),
+            method("()V"),
+            method("(I)V")
+        );
+        let out = decompile(&assemble_one(&source), serde_json::json!({}));
+        assert_eq!(out["success"], true);
+        assert_eq!(
+            out["warnings"],
+            serde_json::json!(["Foo declares field y I more than once", "Foo declares method run()V more than once"])
+        );
+
+        let out = decompile(&assemble_one(&source), serde_json::json!({"warnings_as_errors": true}));
+        assert_eq!(out["success"], false);
+        assert!(out["error"].as_str().unwrap().contains("Foo declares method run()V more than once"));
+
+        // Names which aren't valid Modified UTF-8 are still told apart
+        let source = ".class public Foo\n.super java/lang/Object\n\
+                      .field b'\\xff' I\n.field b'\\xfe' I\n.field b'\\xff' I\n.end class\n";
+        let class = assemble_one(source);
+        let out = decompile(&class, serde_json::json!({"utf8_policy": "raw_escape"}));
+        assert_eq!(out["success"], true);
+        assert_eq!(out["warnings"], serde_json::json!(["Foo declares field \u{FFFD} I more than once"]));
+
+        // The default policy fails on such names instead
+        let out = decompile(&class, serde_json::json!({}));
+        assert_eq!(out["success"], false);
+    }
+
+    #[test]
+    fn test_jar_telemetry() {
+        let hello = assemble_one(HELLO);
+        let lambda = assemble_one(&LAMBDA.replace("Foo", "Bar"));
+        let jar = make_jar(&[("Foo.class", &hello), ("Bar.class", &lambda)]);
+
+        let out = class_request(decompile_jar_json, &jar, serde_json::json!({"telemetry": true}));
+        let classes = out["classes"].as_array().unwrap();
+        let metric = |class: &serde_json::Value, key: &str| class["telemetry"][key].as_u64().unwrap();
+        assert_eq!(classes.iter().map(|c| metric(c, "instructions")).collect::<Vec<_>>(), [4, 4]);
+        for class in classes {
+            assert_eq!(metric(class, "output_bytes"), class["output"].as_str().unwrap().len() as u64);
+        }
+        let total: u64 = classes.iter().map(|c| metric(c, "output_bytes")).sum();
+        assert!(total as usize > hello.len() + lambda.len());
+
+        let out = class_request(decompile_jar_json, &jar, serde_json::json!({}));
+        assert_eq!(out["classes"][0]["telemetry"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_forbidden_opcodes() {
+        let class = assemble_one(
+            ".version 49 0\n.class public Foo\n.super java/lang/Object\n\
+             .method public static sub : ()V\n    .code stack 1 locals 1\n        jsr L4\n        return\n\
+                 L4: astore_0\n        ret 0\n    .end code\n.end method\n.end class\n",
+        );
+        let expected = [
+            "Method sub()V of Foo uses forbidden opcode jsr at offset 0",
+            "Method sub()V of Foo uses forbidden opcode ret at offset 5",
+        ];
+        let out = decompile(&class, serde_json::json!({"forbidden_opcodes": ["JSR", "ret", "athrow"]}));
+        assert_eq!(out["success"], true);
+        assert_eq!(out["warnings"], serde_json::json!(expected));
+
+        let out = decompile(&class, serde_json::json!({"forbidden_opcodes": ["jsr"], "warnings_as_errors": true}));
+        assert_eq!(out["success"], false);
+        assert_eq!(out["error"], expected[0]);
+
+        let out = decompile(&class, serde_json::json!({"forbidden_opcodes": ["athrow"], "warnings_as_errors": true}));
+        assert_eq!(out["success"], true);
+
+        let out = decompile(&class, serde_json::json!({"forbidden_opcodes": ["jsr", "bogus"]}));
+        assert_eq!(out["success"], false);
+        assert_eq!(out["error"], "Unknown opcode bogus in forbidden_opcodes");
+
+        // The wide forms can't be used to get around the check
+        let class = assemble_one(
+            ".version 49 0\n.class public Foo\n.super java/lang/Object\n\
+             .method public static widened : ()V\n    .code stack 1 locals 400\n        wide iinc 300 1\n\
+                     jsr L8\n        return\n    L8: astore_0\n        wide ret 300\n\
+                 .end code\n.end method\n.end class\n",
+        );
+        let out = decompile(&class, serde_json::json!({"forbidden_opcodes": ["ret", "iinc"]}));
+        assert_eq!(
+            out["warnings"],
+            serde_json::json!([
+                "Method widened()V of Foo uses forbidden opcode iinc at offset 0",
+                "Method widened()V of Foo uses forbidden opcode ret at offset 11",
+            ])
+        );
+        let out = decompile(&class, serde_json::json!({"forbidden_opcodes": ["wide"]}));
+        assert_eq!(out["warnings"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_include_class_signature() {
+        let class = assemble_one(
+            ".version 52 0\n.class public Box\n.super java/lang/Object\n.implements java/lang/Comparable\n\
+             .signature \"<T:Ljava/lang/Object;K::Ljava/lang/Comparable<TK;>;:Ljava/io/Serializable;>\
+             Ljava/lang/Object;Ljava/lang/Comparable<LBox<+TT;*>.Inner<[TK;>;>;\"\n.end class\n",
+        );
+        let out = class_request(metadata_json, &class, serde_json::json!({"include_class_signature": true}));
+        let signature = &out["class_signature"];
+        assert!(signature["raw"].as_str().unwrap().starts_with("<T:Ljava/lang/Object;K::"));
+        assert_eq!(
+            signature["type_parameters"],
+            serde_json::json!([
+                {"name": "T", "class_bound": "Ljava/lang/Object;", "interface_bounds": []},
+                {
+                    "name": "K",
+                    "class_bound": null,
+                    "interface_bounds": ["Ljava/lang/Comparable<TK;>;", "Ljava/io/Serializable;"]
+                }
+            ])
+        );
+        assert_eq!(signature["super_type"], "Ljava/lang/Object;");
+        assert_eq!(signature["interfaces"], serde_json::json!(["Ljava/lang/Comparable<LBox<+TT;*>.Inner<[TK;>;>;"]));
+
+        let out = class_request(metadata_json, &assemble_one(HELLO), serde_json::json!({"include_class_signature": true}));
+        assert_eq!(out["class_signature"], serde_json::Value::Null);
+        assert!(out.as_object().unwrap().contains_key("class_signature"));
+        let out = class_request(metadata_json, &assemble_one(HELLO), serde_json::json!({}));
+        assert!(out.get("class_signature").is_none());
+
+        // Pathologically nested signatures are reported as malformed rather than parsed recursively
+        let deep_arrays = format!("Ljava/lang/Object;Ljava/util/List<{}I>;", "[".repeat(60000));
+        let deep_generics = format!("Ljava/lang/Object;{}TT;{}", "Ljava/util/List<".repeat(1000), ">;".repeat(1000));
+        for signature in [deep_arrays, deep_generics] {
+            let source = format!(".class public Box\n.super java/lang/Object\n.signature \"{}\"\n.end class\n", signature);
+            let opts = serde_json::json!({"include_class_signature": true});
+            let out = class_request(metadata_json, &assemble_one(&source), opts);
+            assert_eq!(out["class_signature"]["raw"], signature);
+            assert_eq!(out["class_signature"]["super_type"], serde_json::Value::Null);
+        }
+    }
+
+    #[test]
+    fn test_assemble_empty_source() {
+        let error = "empty source: no class declarations found";
+        for source in ["", " \n\t\r\n  ", "; only a comment\n"] {
+            let out = call(assemble_json, serde_json::json!({"file_path": "Empty.j", "source_code": source}));
+            assert_eq!(out["success"], false);
+            assert_eq!(out["file_path"], "Empty.j");
+            assert_eq!(out["error"], error);
+
+            let out = call(assemble_jar_json, serde_json::json!({"file_path": "Empty.j", "source_code": source}));
+            assert_eq!(out["success"], false);
+            assert_eq!(out["error"], error);
+
+            let fragments = serde_json::json!([{"name": "a.j", "source_fragment": source}]);
+            let out = call(assemble_fragments_json, serde_json::json!({"file_path": "Empty.j", "fragments": fragments}));
+            assert_eq!(out["success"], false);
+            assert_eq!(out["error"], error);
+        }
+
+        let _guard = FFI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        set_chunk_writer(Some(Box::new(|_: &[u8]| true)));
+        let source = "; only a comment\n";
+        assert_eq!(assemble_streaming(source.as_ptr(), source.len()), -2);
+        assert_eq!(read_response(0)["error"], error);
+        set_chunk_writer(None);
+    }
+
+    #[test]
+    fn test_constant_pool_json() {
+        let consts = ".super java/lang/Object\n.const [1] = Long 7\n.const [3] = Utf8 marker";
+        let source = HELLO.replace(".super java/lang/Object", consts);
+        let class = assemble_one(&source);
+        let out = class_request(constant_pool_json, &class, serde_json::json!({}));
+        assert_eq!(out["success"], true);
+        let constants = out["constants"].as_array().unwrap();
+        let count = u16::from_be_bytes([class[8], class[9]]);
+        assert_eq!(out["count"], count);
+        assert_eq!(constants.len(), count as usize - 1);
+        for (i, entry) in constants.iter().enumerate() {
+            assert_eq!(entry["index"], i + 1);
+        }
+
+        // The Long takes up indices 1 and 2
+        assert_eq!(constants[0], serde_json::json!({"index": 1, "tag": "Long", "value": 7}));
+        assert_eq!(constants[1], serde_json::json!({"index": 2, "tag": "Unusable"}));
+        assert_eq!(constants[2], serde_json::json!({"index": 3, "tag": "Utf8", "value": "marker", "length": 6}));
+
+        let entry = |tag: &str| constants.iter().find(|c| c["tag"] == tag).unwrap().clone();
+        let utf8 = |index: serde_json::Value| constants[index.as_u64().unwrap() as usize - 1]["value"].clone();
+        let method = entry("Methodref");
+        assert_eq!(method["class"], "java/io/PrintStream");
+        assert_eq!(method["name"], "println");
+        assert_eq!(method["descriptor"], "(Ljava/lang/Object;)V");
+        assert_eq!(entry("String")["value"], "Hello World!");
+        assert_eq!(utf8(entry("String")["string_index"].clone()), "Hello World!");
+        assert_eq!(utf8(entry("Class")["name_index"].clone()), entry("Class")["name"]);
+
+        // Method handles are resolved through the member they reference
+        let out = class_request(constant_pool_json, &assemble_one(LAMBDA), serde_json::json!({}));
+        let constants = out["constants"].as_array().unwrap();
+        let handle = constants.iter().find(|c| c["tag"] == "MethodHandle" && c["name"] == "lambda$main$0").unwrap();
+        assert_eq!(handle["reference_kind"], 6);
+        assert_eq!(handle["class"], "Foo");
+        assert_eq!(handle["descriptor"], "()V");
+        let reference = &constants[handle["reference_index"].as_u64().unwrap() as usize - 1];
+        assert_eq!(reference["tag"], "Methodref");
+        assert_eq!(reference["name"], "lambda$main$0");
+    }
+}
@@ -0,0 +1,356 @@
+// Structured information about a class, decoded from its attributes rather than read off the disassembly.
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::method_code;
+use crate::krakatau_lib::classfile::attrs::{AttrBody, Attribute, ElementValue};
+use crate::krakatau_lib::classfile::cpool::{Const, ConstPool};
+use crate::krakatau_lib::classfile::parse::{Class, Field};
+use crate::krakatau_lib::parse_utf8;
+
+const ACC_PUBLIC: u16 = 0x0001;
+const ACC_STATIC: u16 = 0x0008;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct MetadataOptions {
+    #[serde(default)]
+    pub include_deprecation: bool,
+    #[serde(default)]
+    pub include_local_variables: bool,
+    #[serde(default)]
+    pub include_permitted_subclasses: bool,
+    #[serde(default)]
+    pub include_nest_info: bool,
+    #[serde(default)]
+    pub include_class_signature: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClassMetadata {
+    pub class_name: Option<String>,
+    pub enclosing_method: Option<EnclosingMethod>,
+    /// Whether the class declares `public static void main(String[])`
+    pub has_main: bool,
+    /// Classes listed by the PermittedSubclasses attribute of a sealed class or interface. Empty if the
+    /// class isn't sealed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub permitted_subclasses: Option<Vec<Option<String>>>,
+    /// Only set if requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nest_info: Option<NestInfo>,
+    /// The class's Signature attribute, null if it has none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class_signature: Option<Option<ClassSignature>>,
+    pub fields: Vec<MemberMetadata>,
+    pub methods: Vec<MemberMetadata>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemberMetadata {
+    pub name: Option<String>,
+    pub descriptor: Option<String>,
+    #[serde(flatten)]
+    pub deprecation: Option<Deprecation>,
+    /// Only set for methods. Empty if the method has no LocalVariableTable, e.g. when compiled without -g.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_variables: Option<Vec<LocalVariable>>,
+}
+
+/// An entry of a LocalVariableTable, along with its generic signature from the LocalVariableTypeTable
+/// if there is a matching entry.
+#[derive(Debug, Serialize)]
+pub struct LocalVariable {
+    pub index: u16,
+    pub name: Option<String>,
+    pub descriptor: Option<String>,
+    pub signature: Option<String>,
+    pub start_pc: u32,
+    pub length: u32,
+}
+
+/// Whether a member is marked deprecated, by either the Deprecated attribute or a @Deprecated
+/// annotation. `since` and `for_removal` come from the annotation, if present.
+#[derive(Debug, Default, Serialize)]
+pub struct Deprecation {
+    pub deprecated: bool,
+    pub since: Option<String>,
+    pub for_removal: Option<bool>,
+}
+
+/// The NestHost and NestMembers attributes, which give nestmates access to each other's private members.
+/// A nest member names its host, while the host lists its members.
+#[derive(Debug, Default, Serialize)]
+pub struct NestInfo {
+    pub nest_host: Option<String>,
+    /// Null for entries which don't resolve to a class name
+    pub nest_members: Vec<Option<String>>,
+}
+
+/// The generic signature of a class, as given by its Signature attribute. Types are left as signature
+/// strings, e.g. `Ljava/util/List<TT;>;`. If the signature is malformed, only `raw` is filled in.
+#[derive(Debug, Default, Serialize)]
+pub struct ClassSignature {
+    pub raw: String,
+    pub type_parameters: Vec<TypeParameter>,
+    pub super_type: Option<String>,
+    pub interfaces: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TypeParameter {
+    pub name: String,
+    /// Null when the only bounds are interfaces, as in `<T::Ljava/lang/Comparable<TT;>;>`
+    pub class_bound: Option<String>,
+    pub interface_bounds: Vec<String>,
+}
+
+/// The EnclosingMethod attribute of a local or anonymous class. `name` and `descriptor` are null
+/// when the class isn't enclosed by a method, e.g. when defined in a field initializer.
+#[derive(Debug, Serialize)]
+pub struct EnclosingMethod {
+    pub class: Option<String>,
+    pub name: Option<String>,
+    pub descriptor: Option<String>,
+}
+
+pub fn utf(cp: &ConstPool, ind: u16) -> Option<String> {
+    cp.utf8(ind).and_then(parse_utf8)
+}
+
+pub fn cls(cp: &ConstPool, ind: u16) -> Option<String> {
+    cp.clsutf(ind).and_then(parse_utf8)
+}
+
+/// The JVM's limit on array dimensions
+const MAX_ARRAY_DIMENSIONS: usize = 255;
+/// Type arguments nested deeper than this are rejected rather than risk running out of stack
+const MAX_TYPE_ARGUMENT_DEPTH: usize = 32;
+
+/// End of the reference type signature starting at `i`, if there is a valid one. `depth` is how many
+/// lists of type arguments it's nested in.
+fn reference_type_end(s: &[u8], i: usize, depth: usize) -> Option<usize> {
+    match *s.get(i)? {
+        b'L' => {
+            let mut i = i + 1;
+            loop {
+                i += s[i..].iter().position(|b| b";<.".contains(b))?;
+                if s[i] == b'<' {
+                    i = type_arguments_end(s, i, depth + 1)?;
+                }
+                match *s.get(i)? {
+                    b';' => return Some(i + 1),
+                    b'.' => i += 1,
+                    _ => return None,
+                }
+            }
+        }
+        b'T' => Some(i + s[i..].iter().position(|&b| b == b';')? + 1),
+        b'[' => {
+            let dimensions = s[i..].iter().take_while(|&&b| b == b'[').count();
+            if dimensions > MAX_ARRAY_DIMENSIONS {
+                return None;
+            }
+            let i = i + dimensions;
+            match *s.get(i)? {
+                b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' => Some(i + 1),
+                _ => reference_type_end(s, i, depth),
+            }
+        }
+        _ => None,
+    }
+}
+
+fn type_arguments_end(s: &[u8], mut i: usize, depth: usize) -> Option<usize> {
+    if depth > MAX_TYPE_ARGUMENT_DEPTH {
+        return None;
+    }
+    i += 1;
+    loop {
+        i = match *s.get(i)? {
+            b'>' => return Some(i + 1),
+            b'*' => i + 1,
+            b'+' | b'-' => reference_type_end(s, i + 1, depth)?,
+            _ => reference_type_end(s, i, depth)?,
+        };
+    }
+}
+
+fn parse_class_signature(raw: &str) -> Option<ClassSignature> {
+    let s = raw.as_bytes();
+    let mut res = ClassSignature {
+        raw: raw.to_owned(),
+        ..Default::default()
+    };
+    let mut i = 0;
+    let take = |i: &mut usize| {
+        let end = reference_type_end(s, *i, 0)?;
+        Some(raw[std::mem::replace(i, end)..end].to_owned())
+    };
+
+    if s.first() == Some(&b'<') {
+        i += 1;
+        while s.get(i) != Some(&b'>') {
+            let start = i;
+            let colon = i + s[i..].iter().position(|&b| b == b':')?;
+            i = colon + 1;
+            let class_bound = if s.get(i) == Some(&b':') { None } else { Some(take(&mut i)?) };
+            let mut interface_bounds = Vec::new();
+            while s.get(i) == Some(&b':') {
+                i += 1;
+                interface_bounds.push(take(&mut i)?);
+            }
+            res.type_parameters.push(TypeParameter {
+                name: raw[start..colon].to_owned(),
+                class_bound,
+                interface_bounds,
+            });
+        }
+        i += 1;
+    }
+
+    res.super_type = Some(take(&mut i)?);
+    while i < s.len() {
+        res.interfaces.push(take(&mut i)?);
+    }
+    Some(res)
+}
+
+fn class_signature(c: &Class) -> Option<ClassSignature> {
+    let raw = c.attrs.iter().find_map(|attr| match attr.body {
+        AttrBody::Signature(ind) => Some(utf(&c.cp, ind).unwrap_or_default()),
+        _ => None,
+    })?;
+    Some(parse_class_signature(&raw).unwrap_or(ClassSignature {
+        raw,
+        ..Default::default()
+    }))
+}
+
+fn deprecation(cp: &ConstPool, attrs: &[Attribute]) -> Deprecation {
+    let mut res = Deprecation::default();
+    for attr in attrs {
+        match &attr.body {
+            AttrBody::Deprecated => res.deprecated = true,
+            AttrBody::RuntimeVisibleAnnotations(annos) | AttrBody::RuntimeInvisibleAnnotations(annos) => {
+                for anno in annos {
+                    if cp.utf8(anno.0) != Some(&b"Ljava/lang/Deprecated;"[..]) {
+                        continue;
+                    }
+                    res.deprecated = true;
+                    for (name, val) in &anno.1 {
+                        match (cp.utf8(*name), val) {
+                            (Some(b"since"), ElementValue::Str(ind)) => res.since = utf(cp, *ind),
+                            (Some(b"forRemoval"), ElementValue::Boolean(ind)) => {
+                                if let Some(Const::Int(v)) = cp.0.get(*ind as usize) {
+                                    res.for_removal = Some(*v != 0);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    res
+}
+
+fn local_variables(cp: &ConstPool, m: &Field) -> Vec<LocalVariable> {
+    let code = match method_code(m) {
+        Some(code) => code,
+        None => return Vec::new(),
+    };
+    let mut vars = Vec::new();
+    let mut types = Vec::new();
+    for attr in &code.attrs {
+        match &attr.body {
+            AttrBody::LocalVariableTable(lines) => vars.extend(lines),
+            AttrBody::LocalVariableTypeTable(lines) => types.extend(lines),
+            _ => {}
+        }
+    }
+
+    vars.into_iter()
+        .map(|v| {
+            // Entries of the two tables correspond when they cover the same variable over the same range
+            let signature = types
+                .iter()
+                .find(|t| t.ind == v.ind && t.start == v.start && t.end == v.end && t.name == v.name)
+                .and_then(|t| utf(cp, t.desc));
+            LocalVariable {
+                index: v.ind,
+                name: utf(cp, v.name),
+                descriptor: utf(cp, v.desc),
+                signature,
+                start_pc: v.start.offset(),
+                length: v.end.offset() - v.start.offset(),
+            }
+        })
+        .collect()
+}
+
+impl MemberMetadata {
+    fn new(cp: &ConstPool, m: &Field, opts: &MetadataOptions) -> Self {
+        Self {
+            name: utf(cp, m.name),
+            descriptor: utf(cp, m.desc),
+            deprecation: opts.include_deprecation.then(|| deprecation(cp, &m.attrs)),
+            local_variables: None,
+        }
+    }
+
+    fn method(cp: &ConstPool, m: &Field, opts: &MetadataOptions) -> Self {
+        Self {
+            local_variables: opts.include_local_variables.then(|| local_variables(cp, m)),
+            ..Self::new(cp, m, opts)
+        }
+    }
+}
+
+impl ClassMetadata {
+    pub fn new(c: &Class, opts: &MetadataOptions) -> Self {
+        let mut enclosing_method = None;
+        let mut permitted_subclasses = Vec::new();
+        let mut nest_info = NestInfo::default();
+        for attr in &c.attrs {
+            match &attr.body {
+                AttrBody::EnclosingMethod(cls_ind, nat_ind) => {
+                    let (name, descriptor) = match c.cp.0.get(*nat_ind as usize) {
+                        Some(Const::NameAndType(n, t)) => (utf(&c.cp, *n), utf(&c.cp, *t)),
+                        _ => (None, None),
+                    };
+                    enclosing_method = Some(EnclosingMethod {
+                        class: cls(&c.cp, *cls_ind),
+                        name,
+                        descriptor,
+                    });
+                }
+                AttrBody::PermittedSubclasses(classes) => {
+                    permitted_subclasses.extend(classes.iter().map(|&ind| cls(&c.cp, ind)));
+                }
+                AttrBody::NestHost(ind) => nest_info.nest_host = cls(&c.cp, *ind),
+                AttrBody::NestMembers(classes) => {
+                    nest_info.nest_members.extend(classes.iter().map(|&ind| cls(&c.cp, ind)));
+                }
+                _ => {}
+            }
+        }
+
+        let has_main = c.methods.iter().any(|m| {
+            m.access & (ACC_PUBLIC | ACC_STATIC) == ACC_PUBLIC | ACC_STATIC
+                && c.cp.utf8(m.name) == Some(&b"main"[..])
+                && c.cp.utf8(m.desc) == Some(&b"([Ljava/lang/String;)V"[..])
+        });
+
+        Self {
+            class_name: cls(&c.cp, c.this),
+            enclosing_method,
+            has_main,
+            permitted_subclasses: opts.include_permitted_subclasses.then_some(permitted_subclasses),
+            nest_info: opts.include_nest_info.then_some(nest_info),
+            class_signature: opts.include_class_signature.then(|| class_signature(c)),
+            fields: c.fields.iter().map(|f| MemberMetadata::new(&c.cp, f, opts)).collect(),
+            methods: c.methods.iter().map(|m| MemberMetadata::method(&c.cp, m, opts)).collect(),
+        }
+    }
+}
@@ -0,0 +1,220 @@
+// The constant pool as structured data, with references to other entries resolved to names.
+use serde::Serialize;
+
+use crate::krakatau_lib::classfile::cpool::{Const, ConstPool};
+use crate::krakatau_lib::parse_utf8;
+use crate::metadata::{cls, utf};
+
+#[derive(Debug, Serialize)]
+pub struct PoolEntry {
+    pub index: u16,
+    #[serde(flatten)]
+    pub value: PoolValue,
+}
+
+/// A constant, tagged with its name from the JVM specification. Resolved names are null if the
+/// referenced entry is missing or of the wrong kind.
+#[derive(Debug, Serialize)]
+#[serde(tag = "tag")]
+pub enum PoolValue {
+    /// The slot following a Long or Double, which can't be referenced
+    Unusable,
+    Utf8 {
+        /// Null if the bytes aren't valid Modified UTF-8
+        value: Option<String>,
+        length: usize,
+    },
+    Integer {
+        value: i32,
+    },
+    /// Non-finite values are null, with the exact value given by `bits`
+    Float {
+        value: f32,
+        bits: u32,
+    },
+    Long {
+        value: i64,
+    },
+    Double {
+        value: f64,
+        bits: u64,
+    },
+    Class {
+        name_index: u16,
+        name: Option<String>,
+    },
+    String {
+        string_index: u16,
+        value: Option<String>,
+    },
+    Fieldref(MemberRef),
+    Methodref(MemberRef),
+    InterfaceMethodref(MemberRef),
+    NameAndType {
+        name_index: u16,
+        descriptor_index: u16,
+        name: Option<String>,
+        descriptor: Option<String>,
+    },
+    /// `class`, `name` and `descriptor` are those of the referenced Fieldref, Methodref or
+    /// InterfaceMethodref
+    MethodHandle {
+        reference_kind: u8,
+        reference_index: u16,
+        class: Option<String>,
+        name: Option<String>,
+        descriptor: Option<String>,
+    },
+    MethodType {
+        descriptor_index: u16,
+        descriptor: Option<String>,
+    },
+    Dynamic(DynamicRef),
+    InvokeDynamic(DynamicRef),
+    Module {
+        name_index: u16,
+        name: Option<String>,
+    },
+    Package {
+        name_index: u16,
+        name: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemberRef {
+    pub class_index: u16,
+    pub name_and_type_index: u16,
+    pub class: Option<String>,
+    pub name: Option<String>,
+    pub descriptor: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DynamicRef {
+    /// Index into the BootstrapMethods attribute, rather than the constant pool
+    pub bootstrap_method_attr_index: u16,
+    pub name_and_type_index: u16,
+    pub name: Option<String>,
+    pub descriptor: Option<String>,
+}
+
+fn name_and_type(cp: &ConstPool, ind: u16) -> (Option<String>, Option<String>) {
+    match cp.0.get(ind as usize) {
+        Some(Const::NameAndType(n, t)) => (utf(cp, *n), utf(cp, *t)),
+        _ => (None, None),
+    }
+}
+
+fn member_ref(cp: &ConstPool, class_index: u16, name_and_type_index: u16) -> MemberRef {
+    let (name, descriptor) = name_and_type(cp, name_and_type_index);
+    MemberRef {
+        class_index,
+        name_and_type_index,
+        class: cls(cp, class_index),
+        name,
+        descriptor,
+    }
+}
+
+// The class, name and descriptor of the member referenced by a Fieldref, Methodref or InterfaceMethodref
+fn member(cp: &ConstPool, ind: u16) -> (Option<String>, Option<String>, Option<String>) {
+    match cp.0.get(ind as usize) {
+        Some(Const::Field(c, nat) | Const::Method(c, nat) | Const::InterfaceMethod(c, nat)) => {
+            let (name, descriptor) = name_and_type(cp, *nat);
+            (cls(cp, *c), name, descriptor)
+        }
+        _ => (None, None, None),
+    }
+}
+
+fn dynamic_ref(cp: &ConstPool, bootstrap_method_attr_index: u16, name_and_type_index: u16) -> DynamicRef {
+    let (name, descriptor) = name_and_type(cp, name_and_type_index);
+    DynamicRef {
+        bootstrap_method_attr_index,
+        name_and_type_index,
+        name,
+        descriptor,
+    }
+}
+
+fn value(cp: &ConstPool, c: &Const) -> PoolValue {
+    match *c {
+        // Past index 0, only the slots after Longs and Doubles are left empty
+        Const::Null => PoolValue::Unusable,
+        Const::Utf8(s) => PoolValue::Utf8 {
+            value: parse_utf8(s.0),
+            length: s.0.len(),
+        },
+        Const::Int(v) => PoolValue::Integer { value: v as i32 },
+        Const::Float(bits) => PoolValue::Float {
+            value: f32::from_bits(bits),
+            bits,
+        },
+        Const::Long(v) => PoolValue::Long { value: v as i64 },
+        Const::Double(bits) => PoolValue::Double {
+            value: f64::from_bits(bits),
+            bits,
+        },
+        Const::Class(ind) => PoolValue::Class {
+            name_index: ind,
+            name: utf(cp, ind),
+        },
+        Const::Str(ind) => PoolValue::String {
+            string_index: ind,
+            value: utf(cp, ind),
+        },
+        Const::Field(cls_ind, nat) => PoolValue::Fieldref(member_ref(cp, cls_ind, nat)),
+        Const::Method(cls_ind, nat) => PoolValue::Methodref(member_ref(cp, cls_ind, nat)),
+        Const::InterfaceMethod(cls_ind, nat) => PoolValue::InterfaceMethodref(member_ref(cp, cls_ind, nat)),
+        Const::NameAndType(n, t) => PoolValue::NameAndType {
+            name_index: n,
+            descriptor_index: t,
+            name: utf(cp, n),
+            descriptor: utf(cp, t),
+        },
+        Const::MethodHandle(kind, ind) => {
+            let (class, name, descriptor) = member(cp, ind);
+            PoolValue::MethodHandle {
+                reference_kind: kind,
+                reference_index: ind,
+                class,
+                name,
+                descriptor,
+            }
+        }
+        Const::MethodType(ind) => PoolValue::MethodType {
+            descriptor_index: ind,
+            descriptor: utf(cp, ind),
+        },
+        Const::Dynamic(bsm, nat) => PoolValue::Dynamic(dynamic_ref(cp, bsm, nat)),
+        Const::InvokeDynamic(bsm, nat) => PoolValue::InvokeDynamic(dynamic_ref(cp, bsm, nat)),
+        Const::Module(ind) => PoolValue::Module {
+            name_index: ind,
+            name: utf(cp, ind),
+        },
+        Const::Package(ind) => PoolValue::Package {
+            name_index: ind,
+            name: utf(cp, ind),
+        },
+    }
+}
+
+/// The entry at `ind`, or None if there is no such entry.
+pub fn constant(cp: &ConstPool, ind: u16) -> Option<PoolValue> {
+    // Index 0 is never a valid reference
+    cp.0.get(ind as usize).filter(|_| ind != 0).map(|c| value(cp, c))
+}
+
+/// Every entry of the pool from index 1 on, with the second slot of each Long and Double included so
+/// that entry `i` is at position `i - 1`.
+pub fn entries(cp: &ConstPool) -> Vec<PoolEntry> {
+    cp.0.iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, c)| PoolEntry {
+            index: i as u16,
+            value: value(cp, c),
+        })
+        .collect()
+}
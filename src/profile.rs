@@ -0,0 +1,97 @@
+// Runtime profiles which assembled classes can be checked against, for projects targeting runtimes
+// older or more limited than the one the assembler itself allows for.
+use crate::analysis::{method_code, method_sig};
+use crate::krakatau_lib::classfile::code::Instr;
+use crate::krakatau_lib::classfile::cpool::Const;
+use crate::krakatau_lib::classfile::parse::Class;
+use crate::metadata::cls;
+
+#[derive(Debug, Clone)]
+pub struct Profile {
+    name: String,
+    max_major: u16,
+    invokedynamic: bool,
+    /// MethodHandle and MethodType constants
+    method_handles: bool,
+    dynamic_constants: bool,
+}
+
+impl Profile {
+    /// Parses a profile name: "jvmN" for Java N (e.g. jvm8, jvm17), or "android". The Android profile
+    /// follows what D8 accepts: class files up to Java 17, with invokedynamic (which it desugars), but
+    /// no dynamic constants.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        if name == "android" {
+            return Ok(Self {
+                name: name.to_owned(),
+                max_major: 61,
+                invokedynamic: true,
+                method_handles: true,
+                dynamic_constants: false,
+            });
+        }
+
+        let java = name
+            .strip_prefix("jvm")
+            .and_then(|v| v.parse::<u16>().ok())
+            .filter(|&v| v >= 1)
+            .and_then(|v| v.checked_add(44).map(|major| (v, major)));
+        match java {
+            Some((version, max_major)) => Ok(Self {
+                name: name.to_owned(),
+                max_major,
+                invokedynamic: version >= 7,
+                method_handles: version >= 7,
+                dynamic_constants: version >= 11,
+            }),
+            None => Err(format!("Unknown target profile {:?}, expected jvmN (e.g. jvm8) or android", name)),
+        }
+    }
+
+    /// Lists everything in an assembled class which the profile doesn't allow.
+    pub fn check(&self, c: &Class) -> Vec<String> {
+        let class_name = cls(&c.cp, c.this).unwrap_or_default();
+        let mut errors = Vec::new();
+        if c.version.0 > self.max_major {
+            errors.push(format!(
+                "{} has class version {}, but {} only supports up to {}",
+                class_name, c.version.0, self.name, self.max_major
+            ));
+        }
+
+        let mut report = |allowed: bool, feature: &str| {
+            if !allowed {
+                errors.push(format!("{} uses {}, which {} doesn't support", class_name, feature, self.name));
+            }
+        };
+        let uses = |f: fn(&Const) -> bool| c.cp.0.iter().any(f);
+        if uses(|k| matches!(k, Const::MethodHandle(..) | Const::MethodType(..))) {
+            report(self.method_handles, "method handle or method type constants");
+        }
+        if uses(|k| matches!(k, Const::Dynamic(..))) {
+            report(self.dynamic_constants, "dynamic constants");
+        }
+
+        if !self.invokedynamic {
+            for m in &c.methods {
+                let Some(code) = method_code(m) else {
+                    continue;
+                };
+                if code
+                    .bytecode
+                    .0
+                    .iter()
+                    .any(|(_, instr)| matches!(instr, Instr::Invokedynamic(_)))
+                {
+                    errors.push(format!(
+                        "Method {} of {} uses invokedynamic, which {} doesn't support",
+                        method_sig(&c.cp, m),
+                        class_name,
+                        self.name
+                    ));
+                }
+            }
+        }
+        errors
+    }
+}
@@ -0,0 +1,299 @@
+// Length driven view of a classfile's top level structure. Unlike the real parser, this never looks
+// inside constants or attribute bodies, so it can take apart (and put back together) classes that
+// fail to disassemble.
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RawAttr<'a> {
+    pub name: u16,
+    pub data: &'a [u8],
+}
+
+#[derive(Debug, Clone)]
+pub struct RawMember<'a> {
+    pub access: u16,
+    pub name: u16,
+    pub desc: u16,
+    pub attrs: Vec<RawAttr<'a>>,
+}
+
+/// A removable piece of a class, as indices into `RawClass`'s lists.
+#[derive(Debug, Clone, Copy)]
+pub enum Part {
+    Method(usize),
+    Field(usize),
+    Attr(usize),
+    MethodAttr(usize, usize),
+    FieldAttr(usize, usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct RawClass<'a> {
+    pub version: (u16, u16),
+    pub cp_count: u16,
+    pub cp: &'a [u8],
+    pub access: u16,
+    pub this: u16,
+    pub super_: u16,
+    pub interfaces: Vec<u16>,
+    pub fields: Vec<RawMember<'a>>,
+    pub methods: Vec<RawMember<'a>>,
+    pub attrs: Vec<RawAttr<'a>>,
+}
+
+struct Cursor<'a>(&'a [u8]);
+impl<'a> Cursor<'a> {
+    fn get(&mut self, n: usize) -> Result<&'a [u8], &'static str> {
+        if n > self.0.len() {
+            return Err("end of data");
+        }
+        let (first, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(first)
+    }
+
+    fn u8(&mut self) -> Result<u8, &'static str> {
+        Ok(self.get(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16, &'static str> {
+        Ok(u16::from_be_bytes(self.get(2)?.try_into().unwrap()))
+    }
+    fn u32(&mut self) -> Result<u32, &'static str> {
+        Ok(u32::from_be_bytes(self.get(4)?.try_into().unwrap()))
+    }
+
+    fn list<T>(&mut self, mut cb: impl FnMut(&mut Self) -> Result<T, &'static str>) -> Result<Vec<T>, &'static str> {
+        let count = self.u16()? as usize;
+        let mut vals = Vec::with_capacity(count);
+        for _ in 0..count {
+            vals.push(cb(self)?);
+        }
+        Ok(vals)
+    }
+
+    fn attr(&mut self) -> Result<RawAttr<'a>, &'static str> {
+        let name = self.u16()?;
+        let len = self.u32()? as usize;
+        Ok(RawAttr { name, data: self.get(len)? })
+    }
+
+    fn pool(&mut self) -> Result<(u16, &'a [u8]), &'static str> {
+        let count = self.u16()?;
+        let start = self.0;
+        let mut slot = 1;
+        while slot < count as u32 {
+            slot += skip_const(self)? as u32;
+        }
+        Ok((count, &start[..start.len() - self.0.len()]))
+    }
+
+    fn member(&mut self) -> Result<RawMember<'a>, &'static str> {
+        Ok(RawMember {
+            access: self.u16()?,
+            name: self.u16()?,
+            desc: self.u16()?,
+            attrs: self.list(Self::attr)?,
+        })
+    }
+}
+
+// Skips a single constant, returning the number of slots it occupies
+fn skip_const(r: &mut Cursor) -> Result<u16, &'static str> {
+    let tag = r.u8()?;
+    let len = match tag {
+        1 => r.u16()? as usize,
+        3 | 4 | 9 | 10 | 11 | 12 | 17 | 18 => 4,
+        5 | 6 => 8,
+        7 | 8 | 16 | 19 | 20 => 2,
+        15 => 3,
+        _ => return Err("Unrecognized constant pool tag"),
+    };
+    r.get(len)?;
+    Ok(if tag == 5 || tag == 6 { 2 } else { 1 })
+}
+
+/// Copies the constant pool of a class, replacing the contents of the Utf8 entries for which `f` returns
+/// new bytes. Every entry keeps its index.
+pub fn map_utf8(
+    cp: &[u8],
+    cp_count: u16,
+    mut f: impl FnMut(u16, &[u8]) -> Option<Vec<u8>>,
+) -> Result<Vec<u8>, &'static str> {
+    let mut r = Cursor(cp);
+    let mut out = Vec::with_capacity(cp.len());
+    let mut slot = 1;
+    while slot < cp_count as u32 {
+        let start = r.0;
+        let width = skip_const(&mut r)?;
+        let raw = &start[..start.len() - r.0.len()];
+        match (raw[0] == 1).then(|| f(slot as u16, &raw[3..])).flatten() {
+            Some(new) => {
+                let len: u16 = new.len().try_into().map_err(|_| "Utf8 constant too long")?;
+                out.push(1);
+                out.extend_from_slice(&len.to_be_bytes());
+                out.extend_from_slice(&new);
+            }
+            None => out.extend_from_slice(raw),
+        }
+        slot += width as u32;
+    }
+    Ok(out)
+}
+
+/// A top level region of a classfile, as a byte range. List sections include their count.
+#[derive(Debug, Serialize)]
+pub struct Section {
+    pub name: &'static str,
+    pub start: usize,
+    pub length: usize,
+}
+
+type Skip = fn(&mut Cursor) -> Result<(), &'static str>;
+
+/// Splits a classfile into its top level sections, in file order.
+pub fn layout(data: &[u8]) -> Result<Vec<Section>, &'static str> {
+    let steps: [(&'static str, Skip); 10] = [
+        ("magic", |r| r.u32().map(drop)),
+        ("version", |r| r.u32().map(drop)),
+        ("constant_pool", |r| r.pool().map(drop)),
+        ("access_flags", |r| r.u16().map(drop)),
+        ("this_class", |r| r.u16().map(drop)),
+        ("super_class", |r| r.u16().map(drop)),
+        ("interfaces", |r| r.list(Cursor::u16).map(drop)),
+        ("fields", |r| r.list(Cursor::member).map(drop)),
+        ("methods", |r| r.list(Cursor::member).map(drop)),
+        ("attributes", |r| r.list(Cursor::attr).map(drop)),
+    ];
+
+    let mut r = Cursor(data);
+    let mut sections = Vec::with_capacity(steps.len());
+    for (name, skip) in steps {
+        let start = data.len() - r.0.len();
+        skip(&mut r)?;
+        sections.push(Section {
+            name,
+            start,
+            length: data.len() - r.0.len() - start,
+        });
+    }
+    if !r.0.is_empty() {
+        return Err("Extra data at end of classfile");
+    }
+    Ok(sections)
+}
+
+impl<'a> RawClass<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, &'static str> {
+        let (class, rest) = Self::parse_prefix(data)?;
+        if !rest.is_empty() {
+            return Err("Extra data at end of classfile");
+        }
+        Ok(class)
+    }
+
+    /// Like `parse`, but also returns any data following the class instead of failing.
+    pub fn parse_prefix(data: &'a [u8]) -> Result<(Self, &'a [u8]), &'static str> {
+        let mut r = Cursor(data);
+        if r.u32()? != 0xCAFEBABE {
+            return Err("Classfile does not start with magic bytes");
+        }
+        let minor = r.u16()?;
+        let major = r.u16()?;
+
+        let (cp_count, cp) = r.pool()?;
+        let access = r.u16()?;
+        let this = r.u16()?;
+        let super_ = r.u16()?;
+        let interfaces = r.list(Cursor::u16)?;
+        let fields = r.list(Cursor::member)?;
+        let methods = r.list(Cursor::member)?;
+        let attrs = r.list(Cursor::attr)?;
+
+        let class = Self {
+            version: (major, minor),
+            cp_count,
+            cp,
+            access,
+            this,
+            super_,
+            interfaces,
+            fields,
+            methods,
+            attrs,
+        };
+        Ok((class, r.0))
+    }
+
+    /// Lists every removable part, whole members before the attributes inside them.
+    pub fn parts(&self) -> Vec<Part> {
+        let mut parts: Vec<_> = (0..self.methods.len()).map(Part::Method).collect();
+        parts.extend((0..self.fields.len()).map(Part::Field));
+        parts.extend((0..self.attrs.len()).map(Part::Attr));
+        for (i, m) in self.methods.iter().enumerate() {
+            parts.extend((0..m.attrs.len()).map(|j| Part::MethodAttr(i, j)));
+        }
+        for (i, f) in self.fields.iter().enumerate() {
+            parts.extend((0..f.attrs.len()).map(|j| Part::FieldAttr(i, j)));
+        }
+        parts
+    }
+
+    pub fn without(&self, part: Part) -> Self {
+        let mut new = self.clone();
+        match part {
+            Part::Method(i) => {
+                new.methods.remove(i);
+            }
+            Part::Field(i) => {
+                new.fields.remove(i);
+            }
+            Part::Attr(i) => {
+                new.attrs.remove(i);
+            }
+            Part::MethodAttr(i, j) => {
+                new.methods[i].attrs.remove(j);
+            }
+            Part::FieldAttr(i, j) => {
+                new.fields[i].attrs.remove(j);
+            }
+        }
+        new
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        fn attrs(out: &mut Vec<u8>, attrs: &[RawAttr]) {
+            out.extend_from_slice(&(attrs.len() as u16).to_be_bytes());
+            for a in attrs {
+                out.extend_from_slice(&a.name.to_be_bytes());
+                out.extend_from_slice(&(a.data.len() as u32).to_be_bytes());
+                out.extend_from_slice(a.data);
+            }
+        }
+        fn members(out: &mut Vec<u8>, members: &[RawMember]) {
+            out.extend_from_slice(&(members.len() as u16).to_be_bytes());
+            for m in members {
+                for v in [m.access, m.name, m.desc] {
+                    out.extend_from_slice(&v.to_be_bytes());
+                }
+                attrs(out, &m.attrs);
+            }
+        }
+
+        let mut out = Vec::with_capacity(self.cp.len() + 1000);
+        out.extend_from_slice(&0xCAFEBABEu32.to_be_bytes());
+        out.extend_from_slice(&self.version.1.to_be_bytes());
+        out.extend_from_slice(&self.version.0.to_be_bytes());
+        out.extend_from_slice(&self.cp_count.to_be_bytes());
+        out.extend_from_slice(self.cp);
+        for v in [self.access, self.this, self.super_, self.interfaces.len() as u16] {
+            out.extend_from_slice(&v.to_be_bytes());
+        }
+        for v in &self.interfaces {
+            out.extend_from_slice(&v.to_be_bytes());
+        }
+        members(&mut out, &self.fields);
+        members(&mut out, &self.methods);
+        attrs(&mut out, &self.attrs);
+        out
+    }
+}
@@ -0,0 +1,196 @@
+// Package relocation, as done by shading tools. Only the Utf8 constants used as class names, descriptors
+// or signatures are rewritten, in place, so the rest of the class is left byte for byte the same.
+use std::collections::HashSet;
+
+use crate::krakatau_lib::classfile::attrs::{AttrBody, Attribute, ElementValue};
+use crate::krakatau_lib::classfile::cpool::Const;
+use crate::krakatau_lib::classfile::{self, parse::Class};
+use crate::krakatau_lib::ParserOptions;
+use crate::raw_class::{self, RawClass};
+
+const PARSE_OPTS: ParserOptions = ParserOptions {
+    no_short_code_attr: false,
+};
+
+struct Relocation<'a> {
+    from: &'a [u8],
+    to: &'a [u8],
+}
+
+impl Relocation<'_> {
+    /// Relocates an internal class name such as org/old/Foo.
+    fn name(&self, name: &[u8], out: &mut Vec<u8>) {
+        match name.strip_prefix(self.from) {
+            Some(rest) if rest.is_empty() || rest[0] == b'/' => {
+                out.extend_from_slice(self.to);
+                out.extend_from_slice(rest);
+            }
+            _ => out.extend_from_slice(name),
+        }
+    }
+
+    /// Relocates the class names in a field or method descriptor, or in a generic signature.
+    fn descriptor(&self, s: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(s.len());
+        // Signatures of generic classes and methods start with a list of type parameters, whose names
+        // (which may well start with L or T) end with a colon.
+        let mut in_params = s.first() == Some(&b'<');
+        let mut at_param = false;
+        let mut depth = 0;
+        let mut i = 0;
+        while i < s.len() {
+            let until = |stops: &[u8]| s[i..].iter().position(|b| stops.contains(b)).map_or(s.len(), |p| i + p);
+            if at_param {
+                let end = until(b":");
+                out.extend_from_slice(&s[i..end]);
+                at_param = false;
+                i = end;
+                continue;
+            }
+            match s[i] {
+                b'L' => {
+                    let end = until(b";<");
+                    out.push(b'L');
+                    self.name(&s[i + 1..end], &mut out);
+                    i = end;
+                    continue;
+                }
+                // Type variables, and inner classes of parameterized types, have plain names
+                b'T' | b'.' => {
+                    let end = until(b";<");
+                    out.extend_from_slice(&s[i..end]);
+                    i = end;
+                    continue;
+                }
+                b'<' => {
+                    depth += 1;
+                    at_param = in_params && depth == 1;
+                }
+                b'>' => {
+                    depth -= 1;
+                    in_params &= depth > 0;
+                }
+                b';' => at_param = in_params && depth == 1 && !matches!(s.get(i + 1), Some(b':' | b'>')),
+                _ => {}
+            }
+            out.push(s[i]);
+            i += 1;
+        }
+        out
+    }
+}
+
+/// Indices of the Utf8 constants used as class names, and as descriptors or signatures.
+#[derive(Default)]
+struct Uses {
+    names: HashSet<u16>,
+    descriptors: HashSet<u16>,
+}
+
+impl Uses {
+    fn element_value(&mut self, v: &ElementValue) {
+        match v {
+            ElementValue::Anno(anno) => self.annotation(anno),
+            ElementValue::Array(vals) => vals.iter().for_each(|v| self.element_value(v)),
+            ElementValue::Enum(desc, _) | ElementValue::Class(desc) => {
+                self.descriptors.insert(*desc);
+            }
+            _ => {}
+        }
+    }
+
+    fn annotation(&mut self, anno: &classfile::attrs::Annotation) {
+        self.descriptors.insert(anno.0);
+        for (_, v) in &anno.1 {
+            self.element_value(v);
+        }
+    }
+
+    fn attrs(&mut self, attrs: &[Attribute]) {
+        for attr in attrs {
+            match &attr.body {
+                AttrBody::AnnotationDefault(v) => self.element_value(v),
+                AttrBody::Code((code, _)) => self.attrs(&code.attrs),
+                AttrBody::LocalVariableTable(lines) | AttrBody::LocalVariableTypeTable(lines) => {
+                    self.descriptors.extend(lines.iter().map(|l| l.desc));
+                }
+                AttrBody::Record(components) => {
+                    for c in components {
+                        self.descriptors.insert(c.desc);
+                        self.attrs(&c.attrs);
+                    }
+                }
+                AttrBody::RuntimeInvisibleAnnotations(annos) | AttrBody::RuntimeVisibleAnnotations(annos) => {
+                    annos.iter().for_each(|a| self.annotation(a));
+                }
+                AttrBody::RuntimeInvisibleParameterAnnotations(params)
+                | AttrBody::RuntimeVisibleParameterAnnotations(params) => {
+                    params.iter().flat_map(|p| &p.0).for_each(|a| self.annotation(a));
+                }
+                AttrBody::RuntimeInvisibleTypeAnnotations(annos) | AttrBody::RuntimeVisibleTypeAnnotations(annos) => {
+                    annos.iter().for_each(|a| self.annotation(&a.anno));
+                }
+                AttrBody::Signature(ind) => {
+                    self.descriptors.insert(*ind);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn new(c: &Class) -> Self {
+        let mut uses = Self::default();
+        for constant in &c.cp.0 {
+            match *constant {
+                Const::Class(ind) | Const::Package(ind) => {
+                    uses.names.insert(ind);
+                }
+                Const::NameAndType(_, ind) | Const::MethodType(ind) => {
+                    uses.descriptors.insert(ind);
+                }
+                _ => {}
+            }
+        }
+        for m in c.fields.iter().chain(&c.methods) {
+            uses.descriptors.insert(m.desc);
+            uses.attrs(&m.attrs);
+        }
+        uses.attrs(&c.attrs);
+        uses
+    }
+}
+
+/// Moves every class under the package `from_prefix` (e.g. org/old) to `to_prefix`, rewriting class
+/// references, descriptors and signatures. String constants are left alone, unless they share a Utf8
+/// entry with a class name.
+pub fn relocate_package(data: &[u8], from_prefix: &str, to_prefix: &str) -> Result<Vec<u8>, String> {
+    let from = from_prefix.trim_end_matches('/');
+    let to = to_prefix.trim_end_matches('/');
+    if from.is_empty() || to.is_empty() {
+        return Err("from_prefix and to_prefix must not be empty".to_string());
+    }
+
+    let raw = RawClass::parse(data).map_err(|e| format!("Class parse error: {}", e))?;
+    let class = classfile::parse(data, PARSE_OPTS).map_err(|e| format!("Class parse error: {:?}", e))?;
+    let uses = Uses::new(&class);
+
+    let relocation = Relocation {
+        from: from.as_bytes(),
+        to: to.as_bytes(),
+    };
+    let pool = raw_class::map_utf8(raw.cp, raw.cp_count, |ind, s| {
+        if uses.descriptors.contains(&ind) || (uses.names.contains(&ind) && s.first() == Some(&b'[')) {
+            Some(relocation.descriptor(s))
+        } else if uses.names.contains(&ind) {
+            let mut out = Vec::with_capacity(s.len());
+            relocation.name(s, &mut out);
+            Some(out)
+        } else {
+            None
+        }
+    })
+    .map_err(|e| format!("Class parse error: {}", e))?;
+
+    let new = RawClass { cp: &pool, ..raw };
+    Ok(new.to_bytes())
+}
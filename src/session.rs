@@ -0,0 +1,56 @@
+// Incremental assembly: sources are kept per class, and only those changed since the last build are reassembled.
+use crate::krakatau_lib::{assemble, AssemblerOptions};
+
+struct Entry {
+    name: String,
+    source: String,
+    // Cleared whenever the source changes
+    output: Option<Vec<(Option<String>, Vec<u8>)>>,
+}
+
+#[derive(Default)]
+pub struct AssembleSession {
+    entries: Vec<Entry>,
+    /// Total number of class sources assembled over the lifetime of the session
+    pub assemble_count: usize,
+}
+
+pub struct BuildResult<'a> {
+    pub classes: Vec<&'a (Option<String>, Vec<u8>)>,
+    pub rebuilt: Vec<String>,
+}
+
+impl AssembleSession {
+    /// Sets the source for a class, leaving it unchanged if identical.
+    pub fn update(&mut self, name: String, source: String) {
+        if let Some(e) = self.entries.iter_mut().find(|e| e.name == name) {
+            if e.source != source {
+                e.source = source;
+                e.output = None;
+            }
+        } else {
+            self.entries.push(Entry {
+                name,
+                source,
+                output: None,
+            });
+        }
+    }
+
+    /// Assembles any changed sources and returns the output of every source, in the order they were first added.
+    pub fn build(&mut self) -> Result<BuildResult, String> {
+        let mut rebuilt = Vec::new();
+        for e in &mut self.entries {
+            if e.output.is_none() {
+                self.assemble_count += 1;
+                let classes = assemble(&e.source, AssemblerOptions {})
+                    .map_err(|err| format!("Assembly error in {}: {:?}", e.name, err))?;
+                e.output = Some(classes);
+                rebuilt.push(e.name.clone());
+            }
+        }
+
+        let classes = self.entries.iter().flat_map(|e| e.output.iter().flatten()).collect();
+        Ok(BuildResult { classes, rebuilt })
+    }
+}
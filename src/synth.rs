@@ -0,0 +1,170 @@
+// Generates tiny classes exercising a single opcode, for use as test fixtures. Each opcode gets just
+// enough surrounding code to keep the operand stack consistent, so that the classes also verify.
+use crate::krakatau_lib::{assemble, AssemblerOptions};
+
+const INDY: &str = "invokedynamic InvokeDynamic invokeStatic Method java/lang/invoke/StringConcatFactory makeConcat \
+    (Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/CallSite; \
+    : concat ()Ljava/lang/String;";
+
+/// Pushes a value of the type with the given opcode prefix.
+fn push(t: u8) -> &'static str {
+    match t {
+        b'l' => "lconst_1",
+        b'f' => "fconst_1",
+        b'd' => "dconst_1",
+        b'a' => "aconst_null",
+        _ => "iconst_1",
+    }
+}
+
+fn pop(t: u8) -> &'static str {
+    match t {
+        b'l' | b'd' => "pop2",
+        _ => "pop",
+    }
+}
+
+fn new_array(t: u8) -> &'static str {
+    match t {
+        b'l' => "newarray long",
+        b'f' => "newarray float",
+        b'd' => "newarray double",
+        b'a' => "anewarray java/lang/Object",
+        b'b' => "newarray byte",
+        b'c' => "newarray char",
+        b's' => "newarray short",
+        _ => "newarray int",
+    }
+}
+
+/// Returns the method's return type and code for the given opcode, or None if it isn't one.
+fn method_code(op: &str) -> Option<(&'static str, String)> {
+    if !op.is_ascii() {
+        return None;
+    }
+    let t = *op.as_bytes().first()?;
+    let rest = &op[1..];
+    let code = match op {
+        "nop" => "nop".to_owned(),
+        "aconst_null" | "iconst_m1" | "iconst_0" | "iconst_1" | "iconst_2" | "iconst_3" | "iconst_4" | "iconst_5"
+        | "fconst_0" | "fconst_1" | "fconst_2" | "lconst_0" | "lconst_1" | "dconst_0" | "dconst_1" => {
+            format!("{}\n{}", op, pop(t))
+        }
+        "bipush" | "sipush" | "ldc" | "ldc_w" => format!("{} 1\npop", op),
+        "ldc2_w" => "ldc2_w 1L\npop2".to_owned(),
+
+        "iload" | "lload" | "fload" | "dload" | "aload" => {
+            format!("{}\n{}store 0\n{} 0\n{}", push(t), t as char, op, pop(t))
+        }
+        "istore" | "lstore" | "fstore" | "dstore" | "astore" => format!("{}\n{} 0", push(t), op),
+        _ if matches!(t, b'i' | b'l' | b'f' | b'd' | b'a') && ["load_0", "load_1", "load_2", "load_3"].contains(&rest) => {
+            format!("{}\n{}store{}\n{}\n{}", push(t), t as char, &rest[4..], op, pop(t))
+        }
+        _ if matches!(t, b'i' | b'l' | b'f' | b'd' | b'a')
+            && ["store_0", "store_1", "store_2", "store_3"].contains(&rest) =>
+        {
+            format!("{}\n{}", push(t), op)
+        }
+        "iinc" => "iconst_0\nistore_0\niinc 0 1".to_owned(),
+        "wide" => "iconst_0\nwide istore 4\nwide iinc 4 1000".to_owned(),
+        "ret" | "jsr" | "jsr_w" => {
+            let jsr = if op == "ret" { "jsr" } else { op };
+            return Some(("V", format!("{} L1\nreturn\nL1:\nastore_0\nret 0", jsr)));
+        }
+
+        _ if matches!(t, b'i' | b'l' | b'f' | b'd' | b'a' | b'b' | b'c' | b's') && rest == "aload" => {
+            format!("iconst_1\n{}\niconst_0\n{}\n{}", new_array(t), op, pop(t))
+        }
+        _ if matches!(t, b'i' | b'l' | b'f' | b'd' | b'a' | b'b' | b'c' | b's') && rest == "astore" => {
+            format!("iconst_1\n{}\niconst_0\n{}\n{}", new_array(t), push(t), op)
+        }
+
+        "pop" => "iconst_0\npop".to_owned(),
+        "pop2" => "lconst_0\npop2".to_owned(),
+        "dup" => "iconst_0\ndup\npop\npop".to_owned(),
+        "dup_x1" => "iconst_0\niconst_0\ndup_x1\npop\npop\npop".to_owned(),
+        "dup_x2" => "lconst_0\niconst_0\ndup_x2\npop\npop2\npop".to_owned(),
+        "dup2" => "lconst_0\ndup2\npop2\npop2".to_owned(),
+        "dup2_x1" => "iconst_0\nlconst_0\ndup2_x1\npop2\npop\npop2".to_owned(),
+        "dup2_x2" => "lconst_0\nlconst_0\ndup2_x2\npop2\npop2\npop2".to_owned(),
+        "swap" => "iconst_0\niconst_1\nswap\npop\npop".to_owned(),
+
+        _ if matches!(t, b'i' | b'l' | b'f' | b'd') && ["add", "sub", "mul", "div", "rem"].contains(&rest) => {
+            format!("{}\n{}\n{}\n{}", push(t), push(t), op, pop(t))
+        }
+        _ if matches!(t, b'i' | b'l') && ["and", "or", "xor"].contains(&rest) => {
+            format!("{}\n{}\n{}\n{}", push(t), push(t), op, pop(t))
+        }
+        _ if matches!(t, b'i' | b'l') && ["shl", "shr", "ushr"].contains(&rest) => {
+            format!("{}\niconst_1\n{}\n{}", push(t), op, pop(t))
+        }
+        _ if matches!(t, b'i' | b'l' | b'f' | b'd') && rest == "neg" => format!("{}\n{}\n{}", push(t), op, pop(t)),
+        "i2l" | "i2f" | "i2d" | "l2i" | "l2f" | "l2d" | "f2i" | "f2l" | "f2d" | "d2i" | "d2l" | "d2f" | "i2b" | "i2c"
+        | "i2s" => format!("{}\n{}\n{}", push(t), op, pop(op.as_bytes()[2])),
+        "lcmp" | "fcmpl" | "fcmpg" | "dcmpl" | "dcmpg" => format!("{}\n{}\n{}\npop", push(t), push(t), op),
+
+        "ifeq" | "ifne" | "iflt" | "ifge" | "ifgt" | "ifle" => format!("iconst_0\n{} L1\nL1:", op),
+        "if_icmpeq" | "if_icmpne" | "if_icmplt" | "if_icmpge" | "if_icmpgt" | "if_icmple" => {
+            format!("iconst_0\niconst_0\n{} L1\nL1:", op)
+        }
+        "if_acmpeq" | "if_acmpne" => format!("aconst_null\naconst_null\n{} L1\nL1:", op),
+        "ifnull" | "ifnonnull" => format!("aconst_null\n{} L1\nL1:", op),
+        "goto" | "goto_w" => format!("{} L1\nL1:", op),
+        "tableswitch" => "iconst_0\ntableswitch 0\nL1\ndefault : L1\nL1:".to_owned(),
+        "lookupswitch" => "iconst_0\nlookupswitch\n0 : L1\ndefault : L1\nL1:".to_owned(),
+
+        "ireturn" => return Some(("I", "iconst_1\nireturn".to_owned())),
+        "lreturn" => return Some(("J", "lconst_1\nlreturn".to_owned())),
+        "freturn" => return Some(("F", "fconst_1\nfreturn".to_owned())),
+        "dreturn" => return Some(("D", "dconst_1\ndreturn".to_owned())),
+        "areturn" => return Some(("Ljava/lang/Object;", "aconst_null\nareturn".to_owned())),
+        "return" => return Some(("V", "return".to_owned())),
+        "athrow" => return Some(("V", "aconst_null\nathrow".to_owned())),
+
+        "getstatic" => "getstatic Field Synth s I\npop".to_owned(),
+        "putstatic" => "iconst_1\nputstatic Field Synth s I".to_owned(),
+        "getfield" => "aconst_null\ngetfield Field Synth f I\npop".to_owned(),
+        "putfield" => "aconst_null\niconst_1\nputfield Field Synth f I".to_owned(),
+        "invokevirtual" | "invokespecial" => format!("aconst_null\n{} Method java/lang/Object hashCode ()I\npop", op),
+        "invokestatic" => "invokestatic Method java/lang/Thread yield ()V".to_owned(),
+        "invokeinterface" => "aconst_null\ninvokeinterface InterfaceMethod java/lang/Runnable run ()V 1".to_owned(),
+        "invokedynamic" => format!("{}\npop", INDY),
+
+        "new" => "new java/lang/Object\npop".to_owned(),
+        "newarray" => "iconst_1\nnewarray int\npop".to_owned(),
+        "anewarray" => "iconst_1\nanewarray java/lang/Object\npop".to_owned(),
+        "multianewarray" => "iconst_1\niconst_1\nmultianewarray [[I 2\npop".to_owned(),
+        "arraylength" => "iconst_1\nnewarray int\narraylength\npop".to_owned(),
+        "checkcast" | "instanceof" => format!("aconst_null\n{} java/lang/String\npop", op),
+        "monitorenter" | "monitorexit" => format!("aconst_null\n{}", op),
+        _ => return None,
+    };
+    Some(("V", format!("{}\nreturn", code)))
+}
+
+/// Assembly for a class named Synth whose only method uses `opcode`.
+pub fn opcode_class_source(opcode: &str) -> Result<String, String> {
+    let (ret, code) = method_code(opcode).ok_or_else(|| format!("Unrecognized opcode {}", opcode))?;
+    // invokedynamic needs at least Java 7, and jsr and ret are only allowed before Java 7. Using Java 5
+    // for everything else means branches don't need stack map frames.
+    let version = if opcode == "invokedynamic" { 52 } else { 49 };
+    let mut source = format!(
+        ".version {} 0\n.class public Synth\n.super java/lang/Object\n.field static s I\n.field f I\n\n\
+         .method public static synth : (){}\n    .code stack 8 locals 5\n",
+        version, ret
+    );
+    for line in code.lines() {
+        let indent = if line.ends_with(':') { "    " } else { "        " };
+        source.push_str(indent);
+        source.push_str(line);
+        source.push('\n');
+    }
+    source.push_str("    .end code\n.end method\n.end class\n");
+    Ok(source)
+}
+
+pub fn synthesize(opcode: &str) -> Result<Vec<u8>, String> {
+    let source = opcode_class_source(opcode)?;
+    let mut classes = assemble(&source, AssemblerOptions {}).map_err(|e| format!("Assembly error: {:?}", e))?;
+    Ok(classes.remove(0).1)
+}
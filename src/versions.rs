@@ -0,0 +1,54 @@
+// Classfile major versions and the Java releases that introduced them.
+
+use serde::Serialize;
+
+/// Newest major version covered by the assembly specification (Java 19). Later versions still
+/// disassemble, since the parser doesn't check versions, but new attributes are treated as opaque.
+pub const MAX_SUPPORTED_MAJOR: u16 = 63;
+
+pub static JAVA_VERSIONS: &[(u16, &str)] = &[
+    (45, "1.1"),
+    (46, "1.2"),
+    (47, "1.3"),
+    (48, "1.4"),
+    (49, "5"),
+    (50, "6"),
+    (51, "7"),
+    (52, "8"),
+    (53, "9"),
+    (54, "10"),
+    (55, "11"),
+    (56, "12"),
+    (57, "13"),
+    (58, "14"),
+    (59, "15"),
+    (60, "16"),
+    (61, "17"),
+    (62, "18"),
+    (63, "19"),
+    (64, "20"),
+    (65, "21"),
+    (66, "22"),
+    (67, "23"),
+    (68, "24"),
+    (69, "25"),
+];
+
+#[derive(Debug, Serialize)]
+pub struct VersionInfo {
+    pub major: u16,
+    pub java: &'static str,
+    /// "supported" or "opaque_new_attributes"
+    pub status: &'static str,
+}
+
+pub fn version_table() -> Vec<VersionInfo> {
+    JAVA_VERSIONS
+        .iter()
+        .map(|&(major, java)| VersionInfo {
+            major,
+            java,
+            status: if major <= MAX_SUPPORTED_MAJOR { "supported" } else { "opaque_new_attributes" },
+        })
+        .collect()
+}